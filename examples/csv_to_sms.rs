@@ -1,9 +1,10 @@
 use csv::Reader;
 use retarus::common::creds::Credentials;
+use retarus::common::limit::RateLimit;
 use retarus::common::transport::blocking;
 use retarus::common::uri::Region;
 use retarus::sms::client::{SmsClient};
-use retarus::sms::models::SmsJob;
+use retarus::sms::models::Message;
 
 use std::fs;
 
@@ -13,8 +14,8 @@ fn read_ad_text() -> String {
     return data;
 }
 
-fn load_list(to_send: String) -> Vec<SmsJob> {
-    let mut jobs = vec![];
+fn load_list(to_send: String) -> Vec<Message> {
+    let mut messages = vec![];
 
     let path = "assets/sms_data.csv";
     let mut rdr = Reader::from_path(path).expect("Could not read file");
@@ -25,38 +26,34 @@ fn load_list(to_send: String) -> Vec<SmsJob> {
         // fill placeholder with surname
         let filled = to_send.replace("{}", &data[1]);
         
-        // create a [SmsJob] via the builder function
-        let job = SmsJob::builder()
-            .add_message(filled, vec![data[2].to_string()])
-            .build();
-
-        jobs.push(job);
+        // create a personalized [Message] for each entry
+        messages.push(Message::new(filled, vec![data[2].to_string()]));
     }
-    return jobs;
+    return messages;
 }
 
 fn main() {
     // Read the advertisement text from the assets folder
     let ad = read_ad_text();
     
-    // load all the jobs into memory
-    let jobs = load_list(ad);
+    // load all the messages into memory
+    let messages = load_list(ad);
 
 
     // create a instance of the sdk
     let sdk = SmsClient::builder()
         .set_region(Region::Europe)
         .set_credentials(Credentials::from_env().expect("You need to export your credentials"))
+        // stay below the contracted throughput, jobs over the limit wait instead of being throttled by the server
+        .set_rate_limit(RateLimit::new(10.0, 20, 4))
         .build();
 
-    let mut ids = vec![];
-
-    // Disptach all the sms jobs via the sdk instance
-    for job in jobs {
-        let res = blocking(sdk.send_sms(job)).expect("Error while sending sms job");
-        ids.push(res.job_id);
+    // Dispatch all messages, the sdk packs them into as few sms jobs as the limits allow
+    let res = blocking(sdk.send_sms_batch(None, messages));
+    for failure in res.failed.iter() {
+        println!("Failed to send sms job: {}", failure.reason);
     }
 
-    println!("Sent all sms")
+    println!("Sent {} sms jobs", res.job_ids.len())
 
 }
@@ -1,6 +1,8 @@
 use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
-use retarus::common::creds::Credentials;
-use retarus::common::transport::blocking;
+use retarus::common::provider::EnvCredentials;
+use retarus::common::phone::{CountryCode, PhoneNumber};
+use retarus::common::prometheus::{self, PrometheusMetrics};
+use retarus::blocking::FaxClient;
 use retarus::common::uri::Region;
 use retarus::fax::client::*;
 use retarus::fax::document::Document;
@@ -9,26 +11,23 @@ use retarus::fax::report::Report;
 use std;
 use std::fs::File;
 use std::io::Write;
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::mpsc::channel;
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
-fn is_string_numeric(str: &&str) -> bool {
-    for c in str.chars() {
-        if !c.is_numeric() {
-            return false;
-        }
-    }
-    return true;
-}
+// Numbers in national format, e.g. "089..." are interpreted as german numbers
+const DEFAULT_COUNTRY_CODE: u16 = 49;
 
 fn create_job(path: PathBuf) -> Result<Option<Job>, Box<dyn std::error::Error>> {
     let x = path.to_str().unwrap().split("/").collect::<Vec<&str>>();
     let filename = x.last().unwrap();
     if filename.contains("_") {
         let y = filename.split("_").collect::<Vec<&str>>();
-        if is_string_numeric(y.first().unwrap()) && y.last().unwrap().contains(".pdf") {
+        let number = PhoneNumber::parse(y.first().unwrap(), CountryCode::new(DEFAULT_COUNTRY_CODE).ok());
+        if let (Ok(number), true) = (number, y.last().unwrap().contains(".pdf")) {
             // Read content of pdf file
             let data = std::fs::read(path.clone()).unwrap();
 
@@ -38,7 +37,7 @@ fn create_job(path: PathBuf) -> Result<Option<Job>, Box<dyn std::error::Error>>
             // Create a job
             let job = Job::builder()
                 .add_document(doc)
-                .add_recipient(y.first().unwrap().to_string())
+                .add_phone_number(number)
                 .build();
             return Ok(Some(job));
         }
@@ -54,6 +53,20 @@ fn write_report(report: Report) {
     println!("Wrote report");
 }
 
+/// Serve the metrics for a local Prometheus agent, the address can be changed with retarus_metrics_addr.
+fn serve_metrics(metrics: Arc<PrometheusMetrics>) {
+    let addr: SocketAddr = std::env::var("retarus_metrics_addr")
+        .unwrap_or_else(|_| "127.0.0.1:9184".to_string())
+        .parse()
+        .expect("retarus_metrics_addr must be an address like 127.0.0.1:9184");
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().expect("Could not start the runtime of the metrics server");
+        if let Err(err) = runtime.block_on(prometheus::serve(metrics, addr)) {
+            eprintln!("The metrics server stopped: {}", err);
+        }
+    });
+}
+
 fn main() {
     let (tx, rx) = channel();
 
@@ -65,18 +78,21 @@ fn main() {
     // below will be monitored for changes.
     watcher.watch("out", RecursiveMode::Recursive).unwrap();
 
-    // Load the credentials from environment variables
-    let user_id = std::env::var("retarus_userid").unwrap();
-    let password = std::env::var("retarus_fax_password").unwrap();
-
     let customer_number = std::env::var("retarus_customer_number").unwrap();
+    let metrics = Arc::new(PrometheusMetrics::new());
+    serve_metrics(metrics.clone());
 
     // create a sdk instance with all needed parameters
     let sdk = ClientSDK::builder()
         .set_region(Region::Europe)
         .set_customer_number(customer_number)
-        .set_credentials(Credentials::new(user_id.as_str(), password.as_str()))
+        // the credentials are read from the environment for every request, so the password can be changed while the daemon runs
+        .set_credential_provider(EnvCredentials::new("retarus_userid", "retarus_fax_password"))
+        .set_metrics(metrics)
+        // the report of a job is on one of the servers of the region, ask all of them at once
+        .set_parallel_lookups(true)
         .build();
+    let sdk = FaxClient::new(sdk).expect("Could not start the runtime of the fax client");
 
     loop {
         // Check for file creations in the out directory
@@ -85,16 +101,26 @@ fn main() {
             Err(e) => panic!("watch error: {:?}", e),
         };
         if let DebouncedEvent::Create(path) = res {
-            let job = create_job(path).unwrap().unwrap();
+            let job = match create_job(path.clone()) {
+                Ok(Some(job)) => job,
+                Ok(None) => {
+                    eprintln!("Ignored {}, the name is not recipient_number_filename.pdf with a valid number", path.display());
+                    continue;
+                }
+                Err(err) => {
+                    eprintln!("Ignored {}: {}", path.display(), err);
+                    continue;
+                }
+            };
 
             // send the fax
-            let res = blocking(sdk.send_job(None, job)).unwrap();
+            let res = sdk.send_job(None, job).unwrap();
             println!("Created and sent Fax");
             let job_id = res.job_id;
             let mut is_processed = false;
             // wait until the job has been processed to create a local copy of the fax report
             while !is_processed {
-                let report_res = blocking(sdk.get_fax_report(job_id.clone(), None)).unwrap();
+                let report_res = sdk.get_fax_report(job_id.clone(), None).unwrap();
                 if report_res
                     .recipient_status
                     .get(0)
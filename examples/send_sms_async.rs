@@ -14,7 +14,7 @@ async fn main() {
 
     let destination_numbers = vec!["+4912310000000".to_string()];
 
-    let sms = SmsJob::builder().add_message("Hello World, this is an example how to use the retarus sms service via the rust sdk.".to_string(), destination_numbers).build();
+    let sms = SmsJob::builder().add_message("Hello World, this is an example how to use the retarus sms service via the rust sdk.".to_string(), destination_numbers).expect("Invalid sms job").build();
     let res = client.send_sms(sms).await.unwrap();
     println!("Server-Response: {:?}", res)
 }
\ No newline at end of file
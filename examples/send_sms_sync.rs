@@ -1,21 +1,20 @@
-use retarus::{sms::{client::{SmsClient}, models::SmsJob}, common::{creds::Credentials, uri::Region, transport::blocking}};
+use retarus::{blocking, sms::{client::{SmsClient}, models::SmsJob}, common::{provider::EnvCredentials, uri::Region}};
 
 
 fn main() {
-    // load exported credentials
-    let user_id = std::env::var("retarus_sms_userid").unwrap();
-    let password = std::env::var("retarus_sms_password").unwrap();
+    // the credentials are read from these exported variables for every request
+    let credentials = EnvCredentials::new("retarus_sms_userid", "retarus_sms_password");
 
-    let credentials = Credentials::new(user_id.as_str(), password.as_str());
-    
     // use the builder to create a SmsClient.
-    let client = SmsClient::builder().set_credentials(credentials).set_region(Region::Europe).build();
+    let client = SmsClient::builder().set_credential_provider(credentials).set_region(Region::Europe).build();
+    // wrap it in the blocking client, it owns the runtime that is used for all calls
+    let client = blocking::SmsClient::new(client).unwrap();
 
     let destination_numbers = vec!["+4912310000000".to_string()];
 
-    let sms = SmsJob::builder().add_message("Hello World, this is an example how to use the retarus sms service via the rust sdk.".to_string(), destination_numbers).build();
+    let sms = SmsJob::builder().add_message("Hello World, this is an example how to use the retarus sms service via the rust sdk.".to_string(), destination_numbers).expect("Invalid sms job").build();
     
-    // Use the shipped blocking function to block the thread and wait for the response
-    let res = blocking(client.send_sms(sms)).unwrap();
+    // The call blocks the thread until the response is received
+    let res = client.send_sms(sms).unwrap();
     println!("Server-Response: {:?}", res)
 }
\ No newline at end of file
@@ -0,0 +1,474 @@
+use std::{error::Error, path::PathBuf, process, time::Duration};
+
+use chrono::{DateTime, Utc};
+use clap::{Args, Parser, Subcommand};
+use retarus::{
+    common::{
+        config::Config,
+        phone::{CountryCode, PhoneNumber},
+    },
+    fax::{
+        client::ClientSDK,
+        document::Document,
+        job::Job,
+        report::Report,
+        responses::{BulkDelete, DeletedReport},
+    },
+    sms::models::{JobReport, Message, SmsFilter},
+    webexpress::{import::ImportResult, job::WebexpressJob},
+};
+use serde::Serialize;
+use tokio::time::Instant;
+
+mod outcome;
+
+use outcome::{FaxStatus, Outcome, EXIT_ERROR};
+
+/// Send faxes and SMS and manage reports and lists with the Retarus services.
+///
+/// Exit codes: 0 success, 1 error, 2 invalid arguments, 3 some recipients failed,
+/// 4 all recipients failed, 5 some recipients are still pending.
+#[derive(Debug, Parser)]
+#[command(name = "retarus", version)]
+struct Cli {
+    /// Print the results as json.
+    #[arg(long, global = true)]
+    json: bool,
+    /// A toml or yaml file with the profiles of the services, the environment overrides it.
+    /// Without a file the settings are only read from the environment.
+    #[arg(long, global = true, env = "retarus_config")]
+    config: Option<PathBuf>,
+    /// The profile of the file, else retarus_profile or the default profile of the file.
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    /// Overrides the configured region, e.g. europe, america, switzerland, singapore or an url.
+    #[arg(long, global = true)]
+    region: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Send faxes and manage fax reports.
+    #[command(subcommand)]
+    Fax(FaxCommand),
+    /// Send SMS and inspect SMS jobs.
+    #[command(subcommand)]
+    Sms(SmsCommand),
+    /// Import WebExpress distribution lists.
+    #[command(subcommand)]
+    Webexpress(WebexpressCommand),
+}
+
+#[derive(Debug, Subcommand)]
+enum FaxCommand {
+    /// Send documents to one or more recipients.
+    Send {
+        /// A recipient, in international format or national format with --country-code.
+        #[arg(long = "to", required = true)]
+        to: Vec<String>,
+        /// Used for numbers in national format, e.g. +49.
+        #[arg(long)]
+        country_code: Option<CountryCode>,
+        #[command(flatten)]
+        customer: CustomerArgs,
+        /// Wait up to this many seconds for the report, the exit code then reflects the delivery to each recipient.
+        /// Without it the submitted recipients are pending.
+        #[arg(long, value_name = "SECONDS")]
+        wait: Option<u64>,
+        /// The documents that are sent, e.g. pdf or txt files.
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Get the reports of the given jobs, or the last reports if no job is given.
+    Report {
+        job_ids: Vec<String>,
+        #[command(flatten)]
+        customer: CustomerArgs,
+    },
+    /// Delete the reports of the given jobs.
+    Delete {
+        #[arg(required = true)]
+        job_ids: Vec<String>,
+        #[command(flatten)]
+        customer: CustomerArgs,
+    },
+    /// Delete all reports, up to 1000 with one call.
+    Prune {
+        #[command(flatten)]
+        customer: CustomerArgs,
+    },
+}
+
+impl FaxCommand {
+    fn customer(&self) -> &CustomerArgs {
+        match self {
+            FaxCommand::Send { customer, .. }
+            | FaxCommand::Report { customer, .. }
+            | FaxCommand::Delete { customer, .. }
+            | FaxCommand::Prune { customer } => customer,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+struct CustomerArgs {
+    /// Overrides the configured customer number.
+    #[arg(long)]
+    customer_number: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+enum SmsCommand {
+    /// Send a text to one or more recipients.
+    Send {
+        #[arg(long = "to", required = true)]
+        to: Vec<String>,
+        #[arg(long)]
+        country_code: Option<CountryCode>,
+        text: String,
+    },
+    /// List the jobs that match the filter.
+    Jobs {
+        /// e.g. 2024-01-31T00:00:00Z
+        #[arg(long)]
+        from: Option<DateTime<Utc>>,
+        #[arg(long)]
+        to: Option<DateTime<Utc>>,
+        /// Only jobs that are still processed.
+        #[arg(long)]
+        open: bool,
+        #[arg(long)]
+        limit: Option<i64>,
+        #[arg(long)]
+        offset: Option<usize>,
+    },
+    /// Show the details of a job.
+    Job { job_id: String },
+}
+
+#[derive(Debug, Subcommand)]
+enum WebexpressCommand {
+    /// Import a csv file as distribution list.
+    Import {
+        #[arg(long)]
+        name: String,
+        #[arg(long, default_value = "")]
+        comment: String,
+        /// Used for numbers in national format, e.g. +49.
+        #[arg(long)]
+        country_code: CountryCode,
+        file: PathBuf,
+    },
+}
+
+/// Recipients of a send command that were not submitted.
+#[derive(Debug, Serialize)]
+struct Rejected {
+    number: String,
+    reason: String,
+}
+
+#[derive(Debug, Serialize)]
+struct SendResult {
+    job_ids: Vec<String>,
+    sent: Vec<String>,
+    rejected: Vec<Rejected>,
+    /// The reports that were waited for.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    reports: Vec<Report>,
+}
+
+/// How often the report of a sent fax is asked for while waiting.
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let code = match run(cli).await {
+        Ok(outcome) => outcome.exit_code(),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            EXIT_ERROR
+        }
+    };
+    process::exit(code);
+}
+
+async fn run(cli: Cli) -> Result<Outcome, Box<dyn Error>> {
+    let mut config = match &cli.config {
+        Some(path) => Config::load(path, cli.profile.as_deref())?,
+        None => Config::from_env(),
+    };
+    if let Some(region) = cli.region {
+        override_region(&mut config, region);
+    }
+    match cli.command {
+        Command::Fax(command) => fax(config, command, cli.json).await,
+        Command::Sms(command) => sms(&config, command, cli.json).await,
+        Command::Webexpress(command) => webexpress(&config, command, cli.json).await,
+    }
+}
+
+/// The region of the command line is used for every service, also for those with their own region.
+fn override_region(config: &mut Config, region: String) {
+    let profile = &mut config.profile;
+    for settings in [&mut profile.fax, &mut profile.sms, &mut profile.webexpress].into_iter().flatten() {
+        settings.region = None;
+    }
+    profile.region = Some(region);
+}
+
+async fn fax(mut config: Config, command: FaxCommand, json: bool) -> Result<Outcome, Box<dyn Error>> {
+    if let (Some(number), Some(fax)) = (command.customer().customer_number.clone(), config.profile.fax.as_mut()) {
+        fax.customer_number = Some(number);
+    }
+    let client = config.fax_client()?;
+    match command {
+        FaxCommand::Send { to, country_code, wait, files, .. } => {
+            let (numbers, mut rejected) = parse_numbers(to, country_code);
+            if numbers.is_empty() {
+                print(json, &SendResult { job_ids: vec![], sent: vec![], rejected, reports: vec![] }, print_send);
+                return Ok(Outcome::Failed);
+            }
+            let mut documents = vec![];
+            for file in files {
+                let name = file.file_name().and_then(|n| n.to_str()).unwrap_or("document").to_string();
+                let data = std::fs::read(&file).map_err(|err| format!("Could not read {}: {}", file.display(), err))?;
+                documents.push(Document::new(name, data, None));
+            }
+            let sent: Vec<String> = numbers.iter().map(PhoneNumber::e164).collect();
+            let job = Job::builder().add_phone_numbers(numbers).add_documents(documents).build();
+            let res = client.send_job(None, job).await?;
+            for number in res.suppressed.iter() {
+                rejected.push(Rejected { number: number.to_string(), reason: "suppressed".to_string() });
+            }
+            let sent: Vec<String> = sent.into_iter().filter(|n| !res.suppressed.contains(n)).collect();
+            let mut result = SendResult { job_ids: vec![res.job_id], sent, rejected, reports: vec![] };
+            // the recipients are only submitted, their delivery is known from the report
+            let mut outcome = Outcome::from_counts(0, result.sent.len(), result.rejected.len());
+            if let Some(wait) = wait.filter(|_| !result.sent.is_empty()) {
+                if let Some(report) = wait_for_report(&client, &result.job_ids[0], Duration::from_secs(wait)).await {
+                    result.reports.push(report);
+                    let statuses = fax_statuses(&result.reports);
+                    let count = |status| statuses.iter().filter(|s| **s == status).count();
+                    outcome = Outcome::from_counts(count(FaxStatus::Ok), count(FaxStatus::Pending), count(FaxStatus::Failed) + result.rejected.len());
+                }
+            }
+            print(json, &result, print_send);
+            Ok(outcome)
+        }
+        FaxCommand::Report { job_ids, .. } => {
+            let reports = match job_ids.len() {
+                0 => client.get_fax_reports(None).await?.reports,
+                1 => vec![client.get_fax_report(job_ids[0].to_string(), None).await?],
+                _ => client.perform_bulk_get(job_ids, None).await?.reports,
+            };
+            print(json, &reports, |reports| reports.iter().for_each(print_report));
+            let statuses = fax_statuses(&reports);
+            let count = |status| statuses.iter().filter(|s| **s == status).count();
+            Ok(Outcome::from_counts(count(FaxStatus::Ok), count(FaxStatus::Pending), count(FaxStatus::Failed)))
+        }
+        FaxCommand::Delete { job_ids, .. } => {
+            let deleted = if job_ids.len() == 1 {
+                vec![client.delete_fax_report(job_ids[0].to_string(), None).await?]
+            } else {
+                client.perform_bulk_delete(job_ids, None).await?.reports
+            };
+            print_deleted(json, &deleted);
+            Ok(deleted_outcome(&deleted))
+        }
+        FaxCommand::Prune { .. } => {
+            let BulkDelete { reports } = client.prune_reports(None).await?;
+            print_deleted(json, &reports);
+            Ok(deleted_outcome(&reports))
+        }
+    }
+}
+
+/// Asks for the report of the job until no recipient is pending or the time is over, None if no report was found until then.
+async fn wait_for_report(client: &ClientSDK, job_id: &str, wait: Duration) -> Option<Report> {
+    let deadline = Instant::now() + wait;
+    let mut report = None;
+    loop {
+        // the report is not found until the job was processed
+        if let Ok(next) = client.get_fax_report(job_id.to_string(), None).await {
+            let done = !fax_statuses(std::slice::from_ref(&next)).contains(&FaxStatus::Pending);
+            report = Some(next);
+            if done {
+                break;
+            }
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        tokio::time::sleep(POLL_INTERVAL.min(deadline - now)).await;
+    }
+    report
+}
+
+fn fax_statuses(reports: &[Report]) -> Vec<FaxStatus> {
+    reports
+        .iter()
+        .flat_map(|r| r.recipient_status.iter().flatten())
+        .map(|s| FaxStatus::from_status(&s.status))
+        .collect()
+}
+
+async fn sms(config: &Config, command: SmsCommand, json: bool) -> Result<Outcome, Box<dyn Error>> {
+    let client = config.sms_client()?;
+    match command {
+        SmsCommand::Send { to, country_code, text } => {
+            let (numbers, mut rejected) = parse_numbers(to, country_code);
+            if numbers.is_empty() {
+                print(json, &SendResult { job_ids: vec![], sent: vec![], rejected, reports: vec![] }, print_send);
+                return Ok(Outcome::Failed);
+            }
+            let dst: Vec<String> = numbers.iter().map(PhoneNumber::e164).collect();
+            let res = client.send_sms_batch(None, vec![Message::new(text, dst.clone())]).await;
+            let mut failed: Vec<String> = res.suppressed.clone();
+            for number in res.suppressed.iter() {
+                rejected.push(Rejected { number: number.to_string(), reason: "suppressed".to_string() });
+            }
+            for failure in res.failed.iter() {
+                for number in failure.job.recipients() {
+                    failed.push(number.to_string());
+                    rejected.push(Rejected { number: number.to_string(), reason: failure.reason.to_string() });
+                }
+            }
+            let sent = dst.into_iter().filter(|n| !failed.contains(n)).collect();
+            let result = SendResult { job_ids: res.job_ids, sent, rejected, reports: vec![] };
+            print(json, &result, print_send);
+            // the recipients are only submitted, their delivery is known from the job
+            Ok(Outcome::from_counts(0, result.sent.len(), result.rejected.len()))
+        }
+        SmsCommand::Jobs { from, to, open, limit, offset } => {
+            let mut filter = SmsFilter::builder();
+            if let Some(from) = from {
+                filter = filter.set_from_ts(from);
+            }
+            if let Some(to) = to {
+                filter = filter.set_to_ts(to);
+            }
+            if open {
+                filter = filter.set_open(true);
+            }
+            if let Some(limit) = limit {
+                filter = filter.set_limit(limit);
+            }
+            if let Some(offset) = offset {
+                filter = filter.set_offset(offset);
+            }
+            let jobs = client.get_sms_jobs(filter.build()).await?;
+            print(json, &jobs, |jobs| jobs.iter().for_each(print_sms_job));
+            Ok(Outcome::Success)
+        }
+        SmsCommand::Job { job_id } => {
+            let job = client.get_sms_job(job_id).await?;
+            print(json, &job, print_sms_job);
+            Ok(if job.finished_ts.is_some() { Outcome::Success } else { Outcome::Pending })
+        }
+    }
+}
+
+async fn webexpress(config: &Config, command: WebexpressCommand, json: bool) -> Result<Outcome, Box<dyn Error>> {
+    let client = config.webexpress_client()?;
+    match command {
+        WebexpressCommand::Import { name, comment, country_code, file } => {
+            let job = WebexpressJob::builder()
+                .set_list_name(name)
+                .set_list_comment(comment)
+                .set_file(file)
+                .set_default_country_code(country_code)
+                .build()?;
+            let result = client.upload_distributor_list(job).await?;
+            print(json, &result, print_import);
+            Ok(if result.success { Outcome::Success } else { Outcome::Failed })
+        }
+    }
+}
+
+/// Numbers that can not be parsed are rejected, the others are sent.
+fn parse_numbers(numbers: Vec<String>, country_code: Option<CountryCode>) -> (Vec<PhoneNumber>, Vec<Rejected>) {
+    let mut parsed = vec![];
+    let mut rejected = vec![];
+    for number in numbers {
+        match PhoneNumber::parse(&number, country_code) {
+            Ok(n) => parsed.push(n),
+            Err(err) => rejected.push(Rejected { number, reason: err.to_string() }),
+        }
+    }
+    (parsed, rejected)
+}
+
+fn deleted_outcome(reports: &[DeletedReport]) -> Outcome {
+    let deleted = reports.iter().filter(|r| r.deleted).count();
+    Outcome::from_counts(deleted, 0, reports.len() - deleted)
+}
+
+fn print<T: Serialize, F: FnOnce(&T)>(json: bool, value: &T, human: F) {
+    if json {
+        println!("{}", serde_json::to_string_pretty(value).expect("results can always be serialized"));
+    } else {
+        human(value);
+    }
+}
+
+fn print_send(result: &SendResult) {
+    for job_id in result.job_ids.iter() {
+        println!("job {}", job_id);
+    }
+    for number in result.sent.iter() {
+        println!("  sent      {}", number);
+    }
+    for rejected in result.rejected.iter() {
+        println!("  rejected  {}: {}", rejected.number, rejected.reason);
+    }
+    result.reports.iter().for_each(print_report);
+}
+
+fn print_report(report: &Report) {
+    println!("job {} ({} pages)", report.job_id, report.pages);
+    for status in report.recipient_status.iter().flatten() {
+        println!("  {:<10} {} {}", status.status, status.number, status.reason);
+    }
+}
+
+fn print_deleted(json: bool, reports: &Vec<DeletedReport>) {
+    print(json, reports, |reports| {
+        for report in reports.iter() {
+            let state = if report.deleted { "deleted" } else { "not deleted" };
+            println!("job {} {} {}", report.job_id, state, report.readon.as_deref().unwrap_or(""));
+        }
+    });
+}
+
+fn print_sms_job(job: &JobReport) {
+    let state = if job.finished_ts.is_some() { "finished" } else { "open" };
+    let recipients = job.recipient_ids.as_ref().map(Vec::len).unwrap_or(0);
+    println!(
+        "job {} {} recipients: {} received: {}",
+        job.job_id,
+        state,
+        recipients,
+        job.receipt_ts.as_deref().unwrap_or("-")
+    );
+}
+
+fn print_import(result: &ImportResult) {
+    println!("{}", if result.success { "imported" } else { "not imported" });
+    if !result.success {
+        println!("{}", result.message);
+    }
+}
+
+#[test]
+fn test_override_region() {
+    let mut config = Config::from_toml("[profiles.prod.fax]\nregion = \"ch\"\n[profiles.prod.sms]", None).unwrap();
+    override_region(&mut config, "http://localhost:8080".to_string());
+    assert_eq!(config.profile.region.as_deref(), Some("http://localhost:8080"));
+    assert_eq!(config.profile.fax.unwrap().region, None);
+    assert!(config.profile.webexpress.is_none());
+}
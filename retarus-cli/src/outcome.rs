@@ -0,0 +1,65 @@
+/// Exit code if the command could not be executed, e.g. invalid config or a failed request. clap uses 2 for invalid arguments.
+pub const EXIT_ERROR: i32 = 1;
+
+/// The result of a command for its recipients, or for the reports and rows it processed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Every recipient was processed successfully.
+    Success,
+    /// Some recipients failed or were suppressed.
+    Partial,
+    /// No recipient was processed successfully.
+    Failed,
+    /// Nothing failed, but some recipients are still processed.
+    Pending,
+}
+impl Outcome {
+    pub fn from_counts(ok: usize, pending: usize, failed: usize) -> Outcome {
+        if failed > 0 && ok == 0 && pending == 0 {
+            Outcome::Failed
+        } else if failed > 0 {
+            Outcome::Partial
+        } else if pending > 0 {
+            Outcome::Pending
+        } else {
+            Outcome::Success
+        }
+    }
+
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Outcome::Success => 0,
+            Outcome::Partial => 3,
+            Outcome::Failed => 4,
+            Outcome::Pending => 5,
+        }
+    }
+}
+
+/// How the status of a recipient in a fax report is counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaxStatus {
+    Ok,
+    Pending,
+    Failed,
+}
+impl FaxStatus {
+    pub fn from_status(status: &str) -> FaxStatus {
+        match status.to_uppercase().as_str() {
+            "OK" => FaxStatus::Ok,
+            "" | "PENDING" | "QUEUED" | "IN_PROGRESS" | "PROCESSING" => FaxStatus::Pending,
+            _ => FaxStatus::Failed,
+        }
+    }
+}
+
+#[test]
+fn test_outcome() {
+    assert_eq!(Outcome::from_counts(2, 0, 0), Outcome::Success);
+    assert_eq!(Outcome::from_counts(0, 0, 0), Outcome::Success);
+    assert_eq!(Outcome::from_counts(1, 1, 1), Outcome::Partial);
+    assert_eq!(Outcome::from_counts(0, 0, 2), Outcome::Failed);
+    assert_eq!(Outcome::from_counts(1, 1, 0).exit_code(), 5);
+    assert_eq!(FaxStatus::from_status("ok"), FaxStatus::Ok);
+    assert_eq!(FaxStatus::from_status("BUSY"), FaxStatus::Failed);
+}
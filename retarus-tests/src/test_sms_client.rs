@@ -38,6 +38,7 @@ fn send_sms() {
     let client = create_client();
     let job = SmsJob::builder()
         .add_message("Hallo Welt".to_string(), vec!["490000000000".to_string()])
+        .unwrap()
         .build();
 
     let res = blocking(client.send_sms(job)).unwrap();
@@ -1,5 +1,5 @@
 #[cfg(test)]
-use retarus::{webexpress::{client::WebexpressClient, job::WebexpressJob}, common::creds::Credentials};#[cfg(test)]
+use retarus::{webexpress::{client::WebexpressClient, job::{Charset, ListType, WebexpressJob}}, common::creds::Credentials};#[cfg(test)]
 
 
 
@@ -7,9 +7,17 @@ use retarus::{webexpress::{client::WebexpressClient, job::WebexpressJob}, common
 async fn test_create_client() {
     let username = std::env::var("webexpress_username").unwrap();
     let password = std::env::var("webexpress_password").unwrap();
-    let client = WebexpressClient::builder().set_credentiale(Credentials::new(username.as_str(), password.as_str())).build();
-    let job = WebexpressJob::minimal("RetarusCustomerTest".to_string(), "Customers with a high order volume".to_string(), "distributionlist".to_string(), "assets/test.csv".to_string(), "utf-8".to_string(), "+49".to_string());
+    let client = WebexpressClient::builder().set_credentials(Credentials::new(username.as_str(), password.as_str())).build();
+    let job = WebexpressJob::builder()
+        .set_list_name("RetarusCustomerTest".to_string())
+        .set_list_comment("Customers with a high order volume".to_string())
+        .set_list_type(ListType::DistributionList)
+        .set_file("assets/test.csv")
+        .set_charset(Charset::Utf8)
+        .set_default_country_code("+49".parse().unwrap())
+        .build()
+        .unwrap();
     let res = client.upload_distributor_list(job).await.unwrap();
     println!("{:?}", res);
-    assert!(res.contains("The list has been successfully imported"))
+    assert!(res.success, "{}", res.message)
 }
\ No newline at end of file
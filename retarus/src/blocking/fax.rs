@@ -0,0 +1,55 @@
+use std::{error::Error, io};
+
+use crate::{
+    common::runtime::SyncRuntime,
+    fax::{
+        client::ClientSDK,
+        job::Job,
+        report::Report,
+        responses::{BulkDelete, BulkGet, DeletedReport, FaxJobResponse},
+    },
+};
+
+/// The sync version of [ClientSDK], see the async client for the documentation of each function.
+pub struct FaxClient {
+    client: ClientSDK,
+    runtime: SyncRuntime,
+}
+impl FaxClient {
+    pub fn new(client: ClientSDK) -> io::Result<FaxClient> {
+        Ok(FaxClient { client, runtime: SyncRuntime::new()? })
+    }
+
+    /// The async client, e.g. to use it in a part of the program that is async.
+    pub fn as_async(&self) -> &ClientSDK {
+        &self.client
+    }
+
+    pub fn send_job(&self, customer_number: Option<String>, job: Job) -> Result<FaxJobResponse, Box<dyn Error>> {
+        self.runtime.block_on(self.client.send_job(customer_number, job))
+    }
+
+    pub fn get_fax_report(&self, job_id: String, customer_number: Option<String>) -> Result<Report, Box<dyn Error>> {
+        self.runtime.block_on(self.client.get_fax_report(job_id, customer_number))
+    }
+
+    pub fn delete_fax_report(&self, job_id: String, customer_number: Option<String>) -> Result<DeletedReport, Box<dyn Error>> {
+        self.runtime.block_on(self.client.delete_fax_report(job_id, customer_number))
+    }
+
+    pub fn get_fax_reports(&self, customer_number: Option<String>) -> Result<BulkGet, Box<dyn Error>> {
+        self.runtime.block_on(self.client.get_fax_reports(customer_number))
+    }
+
+    pub fn perform_bulk_delete(&self, job_ids: Vec<String>, customer_number: Option<String>) -> Result<BulkDelete, Box<dyn Error>> {
+        self.runtime.block_on(self.client.perform_bulk_delete(job_ids, customer_number))
+    }
+
+    pub fn perform_bulk_get(&self, job_ids: Vec<String>, customer_number: Option<String>) -> Result<BulkGet, Box<dyn Error>> {
+        self.runtime.block_on(self.client.perform_bulk_get(job_ids, customer_number))
+    }
+
+    pub fn prune_reports(&self, customer_number: Option<String>) -> Result<BulkDelete, Box<dyn Error>> {
+        self.runtime.block_on(self.client.prune_reports(customer_number))
+    }
+}
@@ -0,0 +1,33 @@
+//! Sync clients for programs that do not use async. Each client owns a small runtime for its connections,
+//! the calls block the current thread and can also be used from inside another runtime.
+//!
+//! ## Example
+//! ```rust,no_run
+//! use retarus::blocking;
+//! use retarus::common::{creds::Credentials, uri::Region};
+//! use retarus::sms::{client::SmsClient, models::SmsJob};
+//!
+//! let client = SmsClient::builder()
+//!     .set_credentials(Credentials::from_env().unwrap())
+//!     .set_region(Region::Europe)
+//!     .build();
+//! let client = blocking::SmsClient::new(client).unwrap();
+//! let job = SmsJob::builder()
+//!     .add_message("Hello World".to_string(), vec!["+4912310000000".to_string()])
+//!     .unwrap()
+//!     .build();
+//! let res = client.send_sms(job).unwrap();
+//! ```
+#[cfg(feature = "fax")]
+mod fax;
+#[cfg(feature = "sms")]
+mod sms;
+#[cfg(feature = "webexpress")]
+mod webexpress;
+
+#[cfg(feature = "fax")]
+pub use fax::FaxClient;
+#[cfg(feature = "sms")]
+pub use sms::SmsClient;
+#[cfg(feature = "webexpress")]
+pub use webexpress::WebexpressClient;
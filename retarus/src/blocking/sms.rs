@@ -0,0 +1,72 @@
+use std::{error::Error, io};
+
+use crate::{
+    common::runtime::SyncRuntime,
+    sms::{
+        client::SmsClient as AsyncSmsClient,
+        models::{BatchResponse, JobReport, JobResponse, Message, Options, SmsFilter, SmsJob},
+    },
+};
+
+/// The sync version of [SmsClient](crate::sms::client::SmsClient), see the async client for the documentation of each function.
+pub struct SmsClient {
+    client: AsyncSmsClient,
+    runtime: SyncRuntime,
+}
+impl SmsClient {
+    pub fn new(client: AsyncSmsClient) -> io::Result<SmsClient> {
+        Ok(SmsClient { client, runtime: SyncRuntime::new()? })
+    }
+
+    /// The async client, e.g. to use it in a part of the program that is async.
+    pub fn as_async(&self) -> &AsyncSmsClient {
+        &self.client
+    }
+
+    pub fn send_sms(&self, job: SmsJob) -> Result<JobResponse, Box<dyn Error>> {
+        self.runtime.block_on(self.client.send_sms(job))
+    }
+
+    pub fn send_sms_batch(&self, options: Option<Options>, messages: Vec<Message>) -> BatchResponse {
+        self.runtime.block_on(self.client.send_sms_batch(options, messages))
+    }
+
+    pub fn get_sms_job(&self, job_id: String) -> Result<JobReport, Box<dyn Error>> {
+        self.runtime.block_on(self.client.get_sms_job(job_id))
+    }
+
+    pub fn get_sms_jobs(&self, filter: SmsFilter) -> Result<Vec<JobReport>, Box<dyn Error>> {
+        self.runtime.block_on(self.client.get_sms_jobs(filter))
+    }
+}
+
+#[tokio::test]
+async fn test_blocking_inside_runtime() {
+    use crate::common::{creds::Credentials, transport::blocking, uri::Region};
+    use hyper::{service::{make_service_fn, service_fn}, Body, Response, Server};
+
+    // the test runtime is blocked by the calls, so the server gets a thread of its own
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        tokio::runtime::Runtime::new().unwrap().block_on(async move {
+            let make_service = make_service_fn(|_| async {
+                Ok::<_, hyper::Error>(service_fn(|_| async {
+                    Ok::<_, hyper::Error>(Response::new(Body::from(r#"{"jobId": "SMS-1", "recipientIds": ["1"]}"#)))
+                }))
+            });
+            let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+            tx.send(server.local_addr()).unwrap();
+            server.await.unwrap();
+        })
+    });
+    let client = AsyncSmsClient::builder()
+        .set_credentials(Credentials::new("user", "password"))
+        .set_region(Region::Custom(format!("http://{}", rx.recv().unwrap())))
+        .build();
+    let client = SmsClient::new(client).unwrap();
+
+    assert_eq!(client.get_sms_job("SMS-1".to_string()).unwrap().job_id, "SMS-1");
+    assert_eq!(blocking(client.as_async().get_sms_job("SMS-1".to_string())).unwrap().job_id, "SMS-1");
+    // dropping the runtime inside of the test runtime must not panic
+    drop(client);
+}
@@ -0,0 +1,30 @@
+use std::{error::Error, io};
+
+use crate::{
+    common::runtime::SyncRuntime,
+    webexpress::{
+        client::WebexpressClient as AsyncWebexpressClient,
+        import::ImportResult,
+        job::WebexpressJob,
+    },
+};
+
+/// The sync version of [WebexpressClient](crate::webexpress::client::WebexpressClient), see the async client for the documentation of each function.
+pub struct WebexpressClient {
+    client: AsyncWebexpressClient,
+    runtime: SyncRuntime,
+}
+impl WebexpressClient {
+    pub fn new(client: AsyncWebexpressClient) -> io::Result<WebexpressClient> {
+        Ok(WebexpressClient { client, runtime: SyncRuntime::new()? })
+    }
+
+    /// The async client, e.g. to use it in a part of the program that is async.
+    pub fn as_async(&self) -> &AsyncWebexpressClient {
+        &self.client
+    }
+
+    pub fn upload_distributor_list(&self, job: WebexpressJob) -> Result<ImportResult, Box<dyn Error>> {
+        self.runtime.block_on(self.client.upload_distributor_list(job))
+    }
+}
@@ -0,0 +1,255 @@
+use std::sync::Arc;
+
+use crate::common::{
+    build_error::BuildError,
+    creds::Credentials,
+    limit::RateLimit,
+    metrics::{Metrics, NoMetrics},
+    provider::CredentialProvider,
+    transport::{HttpPool, TransportConfig},
+    uri::Region,
+};
+#[cfg(feature = "fax")]
+use crate::fax::client::{ClientSDK, ClientSDKBuilder};
+#[cfg(feature = "sms")]
+use crate::sms::client::{SmsClient, SmsClientBuilder};
+#[cfg(feature = "webexpress")]
+use crate::webexpress::client::{WebExpressBuilder, WebexpressClient};
+
+/// The credentials and settings of one service, every service of Retarus uses its own account.
+#[derive(Debug, Clone)]
+pub struct ServiceConfig {
+    /// Fixed credentials are passed to the builder of the client, which rejects the placeholder values.
+    pub(crate) credentials: Option<Credentials>,
+    pub(crate) provider: Option<Arc<dyn CredentialProvider>>,
+    pub(crate) region: Option<Region>,
+    pub(crate) customer_number: Option<String>,
+    pub(crate) rate_limit: Option<RateLimit>,
+}
+impl ServiceConfig {
+    pub fn new(credentials: Credentials) -> ServiceConfig {
+        ServiceConfig { credentials: Some(credentials), provider: None, region: None, customer_number: None, rate_limit: None }
+    }
+
+    /// Ask the provider for the credentials of every request, see [CredentialProvider].
+    pub fn with_provider<P: CredentialProvider + 'static>(provider: P) -> ServiceConfig {
+        ServiceConfig { credentials: None, provider: Some(Arc::new(provider)), region: None, customer_number: None, rate_limit: None }
+    }
+
+    /// Overrides the region of [RetarusBuilder::set_region] for this service.
+    pub fn set_region(mut self, region: Region) -> ServiceConfig {
+        self.region = Some(region);
+        self
+    }
+
+    /// The customer number, only used by the fax service.
+    pub fn set_customer_number(mut self, customer_number: String) -> ServiceConfig {
+        self.customer_number = Some(customer_number);
+        self
+    }
+
+    /// Limit the requests of this service, see [RateLimit].
+    pub fn set_rate_limit(mut self, limit: RateLimit) -> ServiceConfig {
+        self.rate_limit = Some(limit);
+        self
+    }
+
+    /// Pass the credentials or the provider and the rate limit to the builder of a client.
+    pub(crate) fn apply<B>(
+        &self,
+        builder: B,
+        credentials: fn(B, Credentials) -> B,
+        provider: fn(B, Arc<dyn CredentialProvider>) -> B,
+        rate_limit: fn(B, RateLimit) -> B,
+    ) -> B {
+        let builder = match (&self.credentials, &self.provider) {
+            (_, Some(p)) => provider(builder, p.clone()),
+            (Some(c), None) => credentials(builder, c.clone()),
+            (None, None) => builder,
+        };
+        match self.rate_limit {
+            Some(limit) => rate_limit(builder, limit),
+            None => builder,
+        }
+    }
+}
+
+/// One entry point for all services, configured once with the credentials of each service.
+/// The clients share one connection pool and the same timeouts and retries.
+///
+/// ## Example
+/// ```rust
+/// use retarus::{Retarus, ServiceConfig};
+/// use retarus::common::{creds::Credentials, uri::Region};
+///
+/// let retarus = Retarus::builder()
+///     .set_region(Region::Europe)
+///     .set_fax(ServiceConfig::new(Credentials::new("fax_user", "fax_password")).set_customer_number("12345".to_string()))
+///     .set_sms(ServiceConfig::new(Credentials::new("sms_user", "sms_password")))
+///     .build();
+/// assert!(retarus.fax().is_some());
+/// assert!(retarus.webexpress().is_none());
+/// ```
+pub struct Retarus {
+    #[cfg(feature = "fax")]
+    fax: Option<ClientSDK>,
+    #[cfg(feature = "sms")]
+    sms: Option<SmsClient>,
+    #[cfg(feature = "webexpress")]
+    webexpress: Option<WebexpressClient>,
+}
+impl Retarus {
+    pub fn builder() -> RetarusBuilder {
+        RetarusBuilder::default()
+    }
+
+    /// The fax client, None if the fax service was not configured.
+    #[cfg(feature = "fax")]
+    pub fn fax(&self) -> Option<&ClientSDK> {
+        self.fax.as_ref()
+    }
+
+    /// The sms client, None if the sms service was not configured.
+    #[cfg(feature = "sms")]
+    pub fn sms(&self) -> Option<&SmsClient> {
+        self.sms.as_ref()
+    }
+
+    /// The webexpress client, None if the webexpress service was not configured.
+    #[cfg(feature = "webexpress")]
+    pub fn webexpress(&self) -> Option<&WebexpressClient> {
+        self.webexpress.as_ref()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RetarusBuilder {
+    region: Region,
+    transport_config: TransportConfig,
+    metrics: Arc<dyn Metrics>,
+    fax: Option<ServiceConfig>,
+    sms: Option<ServiceConfig>,
+    webexpress: Option<ServiceConfig>,
+}
+impl Default for RetarusBuilder {
+    fn default() -> Self {
+        RetarusBuilder {
+            region: Region::Europe,
+            transport_config: TransportConfig::default(),
+            metrics: Arc::new(NoMetrics),
+            fax: None,
+            sms: None,
+            webexpress: None,
+        }
+    }
+}
+
+impl RetarusBuilder {
+    /// The region of all services that don't set their own region.
+    pub fn set_region(mut self, region: Region) -> RetarusBuilder {
+        self.region = region;
+        self
+    }
+
+    /// Timeouts, retries and connection pooling of all clients.
+    pub fn set_transport_config(mut self, config: TransportConfig) -> RetarusBuilder {
+        self.transport_config = config;
+        self
+    }
+
+    /// Report the measurements of all clients, they are labeled with the service.
+    pub fn set_metrics<M: Metrics + 'static>(mut self, metrics: M) -> RetarusBuilder {
+        self.metrics = Arc::new(metrics);
+        self
+    }
+
+    pub fn set_fax(mut self, config: ServiceConfig) -> RetarusBuilder {
+        self.fax = Some(config);
+        self
+    }
+
+    pub fn set_sms(mut self, config: ServiceConfig) -> RetarusBuilder {
+        self.sms = Some(config);
+        self
+    }
+
+    pub fn set_webexpress(mut self, config: ServiceConfig) -> RetarusBuilder {
+        self.webexpress = Some(config);
+        self
+    }
+
+    /// Build the clients of the configured services, it panics like the builders of the clients, see [RetarusBuilder::try_build].
+    pub fn build(self) -> Retarus {
+        match self.try_build() {
+            Ok(retarus) => retarus,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Build the clients of the configured services, fails e.g. if a service is not available in the region.
+    #[cfg_attr(not(any(feature = "fax", feature = "sms", feature = "webexpress")), allow(unused_variables))]
+    pub fn try_build(self) -> Result<Retarus, BuildError> {
+        let pool = HttpPool::new(&self.transport_config);
+        let region = |service: &ServiceConfig| service.region.clone().unwrap_or_else(|| self.region.clone());
+        Ok(Retarus {
+            #[cfg(feature = "fax")]
+            fax: self.fax.as_ref().map(|fax| {
+                fax.apply(ClientSDK::builder(), ClientSDKBuilder::set_credentials, ClientSDKBuilder::set_credential_provider, ClientSDKBuilder::set_rate_limit)
+                    .set_region(region(fax))
+                    .set_customer_number(fax.customer_number.clone().unwrap_or_default())
+                    .set_transport_config(self.transport_config.clone())
+                    .set_pool(pool.clone())
+                    .set_metrics(self.metrics.clone())
+                    .try_build()
+            }).transpose()?,
+            #[cfg(feature = "sms")]
+            sms: self.sms.as_ref().map(|sms| {
+                sms.apply(SmsClient::builder(), SmsClientBuilder::set_credentials, SmsClientBuilder::set_credential_provider, SmsClientBuilder::set_rate_limit)
+                    .set_region(region(sms))
+                    .set_transport_config(self.transport_config.clone())
+                    .set_pool(pool.clone())
+                    .set_metrics(self.metrics.clone())
+                    .try_build()
+            }).transpose()?,
+            #[cfg(feature = "webexpress")]
+            webexpress: self.webexpress.as_ref().map(|webexpress| {
+                webexpress.apply(WebexpressClient::builder(), WebExpressBuilder::set_credentials, WebExpressBuilder::set_credential_provider, WebExpressBuilder::set_rate_limit)
+                    .set_region(region(webexpress))
+                    .set_transport_config(self.transport_config.clone())
+                    .set_pool(pool.clone())
+                    .set_metrics(self.metrics.clone())
+                    .try_build()
+            }).transpose()?,
+        })
+    }
+}
+
+#[test]
+#[cfg_attr(not(any(feature = "fax", feature = "sms", feature = "webexpress")), allow(unused_variables))]
+fn test_build_retarus() {
+    let service = |user: &str| ServiceConfig::new(Credentials::new(user, "password"));
+    let retarus = Retarus::builder()
+        .set_region(Region::Custom("http://localhost:8080".to_string()))
+        .set_sms(service("sms"))
+        .set_webexpress(service("webexpress").set_region(Region::Europe))
+        .build();
+    #[cfg(feature = "fax")]
+    assert!(retarus.fax().is_none());
+    #[cfg(feature = "sms")]
+    assert!(retarus.sms().is_some());
+    #[cfg(feature = "webexpress")]
+    assert!(retarus.webexpress().is_some());
+
+    #[cfg(feature = "sms")]
+    {
+        let singapore = Retarus::builder().set_region(Region::Singapore).set_sms(service("sms")).try_build();
+        assert_eq!(singapore.err(), Some(BuildError::UnsupportedRegion { service: "sms", region: Region::Singapore }));
+        let placeholder = Retarus::builder().set_sms(ServiceConfig::new(Credentials::default())).try_build();
+        assert_eq!(placeholder.err(), Some(BuildError::PlaceholderCredentials("sms")));
+    }
+    #[cfg(feature = "fax")]
+    {
+        let fax = Retarus::builder().set_fax(service("fax")).try_build();
+        assert_eq!(fax.err(), Some(BuildError::MissingCustomerNumber));
+    }
+}
@@ -0,0 +1,52 @@
+use std::{fmt, sync::Arc};
+
+use super::{creds::Credentials, provider::{CredentialProvider, StaticCredentials}, uri::Region};
+
+/// Returned by the try_build function of the client builders if the configuration can not work.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildError {
+    /// Neither credentials with a username and password nor a credential provider are set.
+    MissingCredentials(&'static str),
+    /// The credentials are the placeholder values of [Credentials::default](super::creds::Credentials::default).
+    PlaceholderCredentials(&'static str),
+    /// The fax service needs a customer number for its endpoints.
+    MissingCustomerNumber,
+    UnsupportedRegion { service: &'static str, region: Region },
+    /// The [RateLimit](super::limit::RateLimit) would block every request.
+    InvalidRateLimit { service: &'static str, reason: &'static str },
+}
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::MissingCredentials(service) => write!(f, "The credentials of the {} client must be set", service),
+            BuildError::PlaceholderCredentials(service) => {
+                write!(f, "The {} client uses the placeholder credentials of Credentials::default(), set your own credentials", service)
+            }
+            BuildError::MissingCustomerNumber => write!(f, "The customer number of the fax client must be set"),
+            BuildError::UnsupportedRegion { service, region } => {
+                write!(f, "The SDK has no endpoint of the {} service in the region {:?}, select Europe or a custom region", service, region)
+            }
+            BuildError::InvalidRateLimit { service, reason } => write!(f, "The rate limit of the {} client is invalid: {}", service, reason),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// The provider if one is set, else the fixed credentials after they are checked.
+pub(crate) fn select_credentials(
+    service: &'static str,
+    provider: Option<Arc<dyn CredentialProvider>>,
+    credentials: Credentials,
+) -> Result<Arc<dyn CredentialProvider>, BuildError> {
+    if let Some(provider) = provider {
+        return Ok(provider);
+    }
+    if credentials.username().is_empty() || credentials.password().is_empty() {
+        return Err(BuildError::MissingCredentials(service));
+    }
+    if credentials.is_placeholder() {
+        return Err(BuildError::PlaceholderCredentials(service));
+    }
+    Ok(Arc::new(StaticCredentials::from(credentials)))
+}
@@ -0,0 +1,434 @@
+use std::{collections::BTreeMap, env, fmt, fs, path::{Path, PathBuf}, time::Duration};
+
+use serde::Deserialize;
+
+#[cfg(feature = "fax")]
+use crate::fax::client::{ClientSDK, ClientSDKBuilder};
+#[cfg(feature = "sms")]
+use crate::sms::client::{SmsClient, SmsClientBuilder};
+#[cfg(feature = "webexpress")]
+use crate::webexpress::client::{WebExpressBuilder, WebexpressClient};
+use crate::client::{Retarus, ServiceConfig};
+
+use super::{
+    build_error::BuildError,
+    creds::{Credentials, Secret},
+    limit::RateLimit,
+    provider::FileCredentials,
+    transport::TransportConfig,
+    uri::{determine_sms_region_uri, determine_webexpress_region_uri, Region},
+};
+
+/// The settings of the clients, read from a toml or yaml file with one profile per environment, or only from the environment with [Config::from_env].
+/// Only available with the `config` feature.
+/// Values of the environment override the file, `{service}` is fax, sms or webexpress:
+/// - retarus_profile, used if no profile is passed to [Config::load]
+/// - retarus_userid, retarus_password, retarus_customer_number and retarus_region for every service of the profile
+/// - retarus_{service}_userid, retarus_{service}_password, retarus_{service}_customer_number and retarus_{service}_region
+///   for one service, they take precedence and add the service to the profile if it is missing
+///
+/// The same variables are read by the `retarus` command line tool.
+///
+/// A service reads its credentials either from username and password, or from the files `username` and `password` in credentials_dir.
+///
+/// ```toml
+/// default_profile = "prod"
+///
+/// [profiles.prod]
+/// region = "europe"
+/// timeout_secs = 30
+///
+/// [profiles.prod.fax]
+/// username = "fax user"
+/// password = "fax password"
+/// customer_number = "12345"
+///
+/// [profiles.prod.sms]
+/// credentials_dir = "/run/secrets/retarus_sms"
+/// rate_limit = { requests_per_second = 10.0, burst = 20, max_in_flight = 4 }
+///
+/// [profiles.local]
+/// region = "http://localhost:8080"
+///
+/// [profiles.local.sms]
+/// username = "test"
+/// password = "test"
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub profile_name: String,
+    pub profile: Profile,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    default_profile: Option<String>,
+    #[serde(default)]
+    profiles: BTreeMap<String, Profile>,
+}
+
+/// The settings of one environment, e.g. prod, staging or a local stand-in of the services.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// The region of all services that don't set their own, europe if it is not set.
+    pub region: Option<String>,
+    pub timeout_secs: Option<u64>,
+    pub connect_retries: Option<u32>,
+    /// Failed calls in a row after which a server is skipped, see [TransportConfig::circuit_failures].
+    pub circuit_failures: Option<u32>,
+    pub circuit_open_secs: Option<u64>,
+    pub fax: Option<ServiceSettings>,
+    pub sms: Option<ServiceSettings>,
+    pub webexpress: Option<ServiceSettings>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServiceSettings {
+    pub username: Option<String>,
+    pub password: Option<Secret>,
+    pub credentials_dir: Option<PathBuf>,
+    /// Required by the fax service.
+    pub customer_number: Option<String>,
+    pub region: Option<String>,
+    pub rate_limit: Option<RateLimit>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// The file could not be read, contains the path and the reason.
+    Unreadable(String, String),
+    /// Only files ending with .toml, .yaml or .yml are supported.
+    UnsupportedFormat(String),
+    /// The file is not valid, contains the message of the parser.
+    Invalid(String),
+    UnknownProfile { profile: String, available: Vec<String> },
+    /// No profile was selected and the file contains more than one, contains the available profiles.
+    NoProfile(Vec<String>),
+    MissingService(&'static str),
+    MissingField { service: &'static str, field: &'static str },
+    InvalidRegion { service: &'static str, reason: String },
+    UnsupportedRegion { service: &'static str, region: Region },
+    /// The settings are complete, but the client refused them.
+    Build(BuildError),
+}
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Unreadable(path, reason) => write!(f, "The config {} could not be read: {}", path, reason),
+            ConfigError::UnsupportedFormat(path) => write!(f, "The config {} must end with .toml, .yaml or .yml", path),
+            ConfigError::Invalid(reason) => write!(f, "The config is invalid: {}", reason),
+            ConfigError::UnknownProfile { profile, available } => {
+                write!(f, "The profile {} does not exist, available profiles: {}", profile, available.join(", "))
+            }
+            ConfigError::NoProfile(available) => {
+                write!(f, "Select one of the profiles {} with retarus_profile or default_profile", available.join(", "))
+            }
+            ConfigError::MissingService(service) => write!(f, "The {} service is not configured in the profile", service),
+            ConfigError::MissingField { service, field } => write!(f, "The {} of the {} service is missing", field, service),
+            ConfigError::InvalidRegion { service, reason } => write!(f, "The region of the {} service is invalid: {}", service, reason),
+            ConfigError::UnsupportedRegion { service, region } => write!(f, "The {} service is not available in the region {:?}", service, region),
+            ConfigError::Build(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Read the file, select the profile and apply the environment. The format is chosen by the extension of the file.
+    pub fn load<P: AsRef<Path>>(path: P, profile: Option<&str>) -> Result<Config, ConfigError> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).map_err(|err| ConfigError::Unreadable(path.display().to_string(), err.to_string()))?;
+        let profile = profile.map(str::to_string).or_else(|| env::var("retarus_profile").ok());
+        let config = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Config::from_toml(&content, profile.as_deref())?,
+            Some("yaml") | Some("yml") => Config::from_yaml(&content, profile.as_deref())?,
+            _ => return Err(ConfigError::UnsupportedFormat(path.display().to_string())),
+        };
+        Ok(config.apply_env())
+    }
+
+    /// Parse a toml config without applying the environment.
+    pub fn from_toml(content: &str, profile: Option<&str>) -> Result<Config, ConfigError> {
+        let file: ConfigFile = toml::from_str(content).map_err(|err| ConfigError::Invalid(err.to_string()))?;
+        file.select(profile)
+    }
+
+    /// Parse a yaml config without applying the environment.
+    pub fn from_yaml(content: &str, profile: Option<&str>) -> Result<Config, ConfigError> {
+        let file: ConfigFile = serde_norway::from_str(content).map_err(|err| ConfigError::Invalid(err.to_string()))?;
+        file.select(profile)
+    }
+
+    /// Only the environment variables listed at [Config], every service is configured and reports the missing values when its client is built.
+    pub fn from_env() -> Config {
+        Config::env_profile().apply_env()
+    }
+
+    fn env_profile() -> Config {
+        let service = Some(ServiceSettings::default());
+        let profile = Profile { fax: service.clone(), sms: service.clone(), webexpress: service, ..Profile::default() };
+        Config { profile_name: "env".to_string(), profile }
+    }
+
+    /// Override the settings with the environment variables listed at [Config].
+    pub fn apply_env(self) -> Config {
+        self.with_env(|name| env::var(name).ok())
+    }
+
+    fn with_env<F: Fn(&str) -> Option<String>>(mut self, var: F) -> Config {
+        let var = |name: String| var(&name).filter(|value| !value.is_empty());
+        if let Some(region) = var("retarus_region".to_string()) {
+            self.profile.region = Some(region);
+        }
+        let shared_username = var("retarus_userid".to_string());
+        let shared_password = var("retarus_password".to_string());
+        let shared_customer_number = var("retarus_customer_number".to_string());
+        let services = [("fax", &mut self.profile.fax), ("sms", &mut self.profile.sms), ("webexpress", &mut self.profile.webexpress)];
+        for (service, settings) in services {
+            let username = var(format!("retarus_{}_userid", service));
+            let password = var(format!("retarus_{}_password", service));
+            let customer_number = var(format!("retarus_{}_customer_number", service));
+            let region = var(format!("retarus_{}_region", service));
+            let own = username.is_some() || password.is_some() || customer_number.is_some() || region.is_some();
+            if settings.is_none() && !own {
+                continue;
+            }
+            let username = username.or(shared_username.clone());
+            let password = password.or(shared_password.clone()).map(Secret::new);
+            let customer_number = customer_number.or(shared_customer_number.clone());
+            let settings = settings.get_or_insert_with(ServiceSettings::default);
+            if username.is_some() || password.is_some() {
+                settings.credentials_dir = None;
+            }
+            settings.username = username.or(settings.username.take());
+            settings.password = password.or(settings.password.take());
+            settings.customer_number = customer_number.or(settings.customer_number.take());
+            settings.region = region.or(settings.region.take());
+        }
+        self
+    }
+
+    pub fn transport_config(&self) -> TransportConfig {
+        let default = TransportConfig::default();
+        TransportConfig {
+            timeout: self.profile.timeout_secs.map(Duration::from_secs).unwrap_or(default.timeout),
+            connect_retries: self.profile.connect_retries.unwrap_or(default.connect_retries),
+            circuit_failures: self.profile.circuit_failures.unwrap_or(default.circuit_failures),
+            circuit_open: self.profile.circuit_open_secs.map(Duration::from_secs).unwrap_or(default.circuit_open),
+            ..default
+        }
+    }
+
+    /// Validate the settings of a service, None if the profile does not configure it.
+    fn service(&self, service: &'static str) -> Result<Option<ServiceConfig>, ConfigError> {
+        let settings = match service {
+            "fax" => &self.profile.fax,
+            "sms" => &self.profile.sms,
+            _ => &self.profile.webexpress,
+        };
+        let settings = match settings {
+            Some(settings) => settings,
+            None => return Ok(None),
+        };
+        let region = match settings.region.as_ref().or(self.profile.region.as_ref()) {
+            Some(region) => region.parse().map_err(|reason| ConfigError::InvalidRegion { service, reason })?,
+            None => Region::Europe,
+        };
+        let supported = match service {
+            "sms" => determine_sms_region_uri(region.clone()).is_some(),
+            "webexpress" => determine_webexpress_region_uri(region.clone()).is_some(),
+            _ => true,
+        };
+        if !supported {
+            return Err(ConfigError::UnsupportedRegion { service, region });
+        }
+        let config = match (&settings.credentials_dir, &settings.username, &settings.password) {
+            (Some(dir), _, _) => ServiceConfig::with_provider(FileCredentials::from_dir(dir)),
+            (None, Some(username), Some(password)) if !username.is_empty() && !password.is_empty() => {
+                ServiceConfig::new(Credentials::new(username, password.expose()))
+            }
+            (None, Some(username), _) if !username.is_empty() => return Err(ConfigError::MissingField { service, field: "password" }),
+            _ => return Err(ConfigError::MissingField { service, field: "username" }),
+        };
+        let mut config = config.set_region(region);
+        match &settings.customer_number {
+            Some(customer_number) if !customer_number.is_empty() => config = config.set_customer_number(customer_number.clone()),
+            _ if service == "fax" => return Err(ConfigError::MissingField { service, field: "customer_number" }),
+            _ => {}
+        }
+        if let Some(limit) = settings.rate_limit {
+            config = config.set_rate_limit(limit);
+        }
+        Ok(Some(config))
+    }
+
+    fn required(&self, service: &'static str) -> Result<ServiceConfig, ConfigError> {
+        self.service(service)?.ok_or(ConfigError::MissingService(service))
+    }
+
+    #[cfg(feature = "fax")]
+    pub fn fax_client(&self) -> Result<ClientSDK, ConfigError> {
+        let fax = self.required("fax")?;
+        fax.apply(ClientSDK::builder(), ClientSDKBuilder::set_credentials, ClientSDKBuilder::set_credential_provider, ClientSDKBuilder::set_rate_limit)
+            .set_region(fax.region.unwrap_or(Region::Europe))
+            .set_customer_number(fax.customer_number.unwrap_or_default())
+            .set_transport_config(self.transport_config())
+            .try_build()
+            .map_err(ConfigError::Build)
+    }
+
+    #[cfg(feature = "sms")]
+    pub fn sms_client(&self) -> Result<SmsClient, ConfigError> {
+        let sms = self.required("sms")?;
+        sms.apply(SmsClient::builder(), SmsClientBuilder::set_credentials, SmsClientBuilder::set_credential_provider, SmsClientBuilder::set_rate_limit)
+            .set_region(sms.region.unwrap_or(Region::Europe))
+            .set_transport_config(self.transport_config())
+            .try_build()
+            .map_err(ConfigError::Build)
+    }
+
+    #[cfg(feature = "webexpress")]
+    pub fn webexpress_client(&self) -> Result<WebexpressClient, ConfigError> {
+        let webexpress = self.required("webexpress")?;
+        webexpress.apply(WebexpressClient::builder(), WebExpressBuilder::set_credentials, WebExpressBuilder::set_credential_provider, WebExpressBuilder::set_rate_limit)
+            .set_region(webexpress.region.unwrap_or(Region::Europe))
+            .set_transport_config(self.transport_config())
+            .try_build()
+            .map_err(ConfigError::Build)
+    }
+
+    /// A [Retarus] instance with all services of the profile, the services share one connection pool.
+    pub fn retarus(&self) -> Result<Retarus, ConfigError> {
+        let mut builder = Retarus::builder().set_transport_config(self.transport_config());
+        if let Some(fax) = self.service("fax")? {
+            builder = builder.set_fax(fax);
+        }
+        if let Some(sms) = self.service("sms")? {
+            builder = builder.set_sms(sms);
+        }
+        if let Some(webexpress) = self.service("webexpress")? {
+            builder = builder.set_webexpress(webexpress);
+        }
+        builder.try_build().map_err(ConfigError::Build)
+    }
+}
+
+impl ConfigFile {
+    /// The given profile, else the default profile, else the only profile of the file.
+    fn select(mut self, profile: Option<&str>) -> Result<Config, ConfigError> {
+        let available: Vec<String> = self.profiles.keys().cloned().collect();
+        let name = match profile.map(str::to_string).or(self.default_profile) {
+            Some(name) => name,
+            None if available.len() == 1 => available[0].clone(),
+            None => return Err(ConfigError::NoProfile(available)),
+        };
+        match self.profiles.remove(&name) {
+            Some(profile) => Ok(Config { profile_name: name, profile }),
+            None => Err(ConfigError::UnknownProfile { profile: name, available }),
+        }
+    }
+}
+
+#[cfg(test)]
+const TEST_CONFIG: &str = r#"
+default_profile = "prod"
+
+[profiles.prod]
+region = "europe"
+timeout_secs = 30
+circuit_open_secs = 10
+
+[profiles.prod.fax]
+username = "fax user"
+password = "fax password"
+customer_number = "12345"
+
+[profiles.prod.sms]
+username = "sms user"
+password = "sms password"
+region = "sg"
+
+[profiles.local.webexpress]
+username = "test"
+password = "test"
+region = "http://localhost:8080"
+rate_limit = { requests_per_second = 5.0, burst = 10, max_in_flight = 2 }
+"#;
+
+#[test]
+fn test_load_profiles() {
+    let config = Config::from_toml(TEST_CONFIG, None).unwrap();
+    assert_eq!(config.profile_name, "prod");
+    assert_eq!(config.transport_config().timeout, Duration::from_secs(30));
+    assert_eq!(config.transport_config().circuit_open, Duration::from_secs(10));
+    #[cfg(feature = "fax")]
+    assert!(config.fax_client().is_ok());
+    #[cfg(feature = "sms")]
+    assert_eq!(config.sms_client().err(), Some(ConfigError::UnsupportedRegion { service: "sms", region: Region::Singapore }));
+    #[cfg(feature = "webexpress")]
+    assert_eq!(config.webexpress_client().err(), Some(ConfigError::MissingService("webexpress")));
+    assert!(!format!("{:?}", config).contains("fax password"));
+
+    #[cfg(feature = "webexpress")]
+    {
+        let local = Config::from_toml(TEST_CONFIG, Some("local")).unwrap();
+        assert!(local.webexpress_client().is_ok());
+        assert!(local.retarus().unwrap().webexpress().is_some());
+    }
+    assert_eq!(
+        Config::from_toml(TEST_CONFIG, Some("staging")).err(),
+        Some(ConfigError::UnknownProfile { profile: "staging".to_string(), available: vec!["local".to_string(), "prod".to_string()] })
+    );
+    assert!(matches!(Config::from_toml("[profiles.prod]\nregoin = \"eu\"", None), Err(ConfigError::Invalid(_))));
+
+    #[cfg(feature = "sms")]
+    {
+        let limited = "[profiles.prod.sms]\nusername = \"u\"\npassword = \"p\"\nrate_limit = { requests_per_second = 0.0, burst = 1, max_in_flight = 1 }";
+        let reason = "requests_per_second must be a positive number";
+        assert_eq!(Config::from_toml(limited, None).unwrap().sms_client().err(), Some(ConfigError::Build(BuildError::InvalidRateLimit { service: "sms", reason })));
+    }
+}
+
+#[test]
+fn test_env_overrides_profile() {
+    let yaml = "profiles:\n  staging:\n    fax:\n      username: fax\n      password: secret\n";
+    let config = Config::from_yaml(yaml, None).unwrap();
+    #[cfg(feature = "fax")]
+    assert_eq!(config.fax_client().err(), Some(ConfigError::MissingField { service: "fax", field: "customer_number" }));
+
+    let config = config.with_env(|name| match name {
+        "retarus_fax_customer_number" => Some("12345".to_string()),
+        "retarus_sms_userid" => Some("sms".to_string()),
+        "retarus_sms_region" => Some("http://localhost:8080".to_string()),
+        "retarus_region" => Some("mars".to_string()),
+        _ => None,
+    });
+    assert_eq!(config.profile.fax.as_ref().unwrap().customer_number, Some("12345".to_string()));
+    #[cfg(feature = "sms")]
+    assert_eq!(config.sms_client().err(), Some(ConfigError::MissingField { service: "sms", field: "password" }));
+    #[cfg(feature = "fax")]
+    assert!(matches!(config.fax_client(), Err(ConfigError::InvalidRegion { service: "fax", .. })));
+
+    // the shared variables fill the services of the profile, the variables of a service take precedence
+    let shared = |name: &str| match name {
+        "retarus_userid" => Some("user".to_string()),
+        "retarus_password" => Some("secret".to_string()),
+        "retarus_customer_number" => Some("12345".to_string()),
+        "retarus_sms_password" => Some("sms secret".to_string()),
+        _ => None,
+    };
+    let config = Config::from_yaml(yaml, None).unwrap().with_env(shared);
+    assert_eq!(config.profile.fax.as_ref().unwrap().customer_number, Some("12345".to_string()));
+    let sms = config.profile.sms.as_ref().unwrap();
+    assert_eq!((sms.username.as_deref(), sms.password.as_ref().map(Secret::expose)), (Some("user"), Some("sms secret")));
+    assert!(config.profile.webexpress.is_none());
+
+    #[cfg(feature = "webexpress")]
+    assert!(Config::env_profile().with_env(shared).webexpress_client().is_ok());
+    #[cfg(feature = "sms")]
+    assert_eq!(Config::env_profile().with_env(|_| None).sms_client().err(), Some(ConfigError::MissingField { service: "sms", field: "username" }));
+}
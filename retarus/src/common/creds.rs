@@ -1,39 +1,150 @@
-use std::{env, error::Error};
+use std::{env, error::Error, fmt};
 
+use zeroize::{Zeroize, Zeroizing};
+
+
+/// A string that must not be logged, e.g. a password. It is printed as `***` by `Debug` and `Display`
+/// and the memory is overwritten with zeros when it is dropped.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret(String);
+impl Secret {
+    pub fn new(value: String) -> Secret {
+        Secret(value)
+    }
+
+    /// The plain value, only use it to send it to the server.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Secret(value.to_string())
+    }
+}
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Secret(value)
+    }
+}
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("***")
+    }
+}
+/// Allows to read a password from a config file of the `config` feature.
+impl<'de> serde::Deserialize<'de> for Secret {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <String as serde::Deserialize>::deserialize(deserializer).map(Secret)
+    }
+}
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+
+const PLACEHOLDER_USERNAME: &str = "exmapleUsername";
+const PLACEHOLDER_PASSWORD: &str = "yourPassword";
 
 /// the Credentails struct should contain the username and password to authorize the requests sent to the server.
+/// The value of the Authorization header is computed once, the password is not copied for every request.
+/// The former public fields `username` and `password` are private, read them with [Credentials::username] and [Credentials::password].
 /// 
 /// ## Example
 /// ```rust
 /// use retarus::common::creds::Credentials;
 /// 
 /// let creds = Credentials::new("abc", "password123");
+/// assert_eq!(format!("{:?}", creds), "Credentials { username: \"abc\", password: Secret(***) }");
 /// ```
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct Credentials {
-    pub username: String,
-    pub password: String
+    username: String,
+    secret: Secret,
+    authorization: Secret,
 }
 impl <'a> Credentials {
     pub fn new(username: &'a str, password: &'a str) -> Credentials {
-        Credentials { username: username.to_string(), password: password.to_string()}
+        Credentials {
+            username: username.to_string(),
+            secret: Secret::from(password),
+            authorization: Secret::new(format!("Basic {}", encode(username, password).as_str())),
+        }
     }
+    /// Placeholder values, the builders of the clients refuse to use them.
     pub fn default() -> Credentials {
-        Credentials{ username: "exmapleUsername".to_string(), password: "yourPassword".to_string()}
+        Credentials::new(PLACEHOLDER_USERNAME, PLACEHOLDER_PASSWORD)
     }
 
     /// Create a [Credentials] instance from env. Before you use this function, you need to export following keys:
     /// - retarus_userid
     /// - retarus_password
+    ///
+    /// To use other variables or to read them for every request, use [EnvCredentials](super::provider::EnvCredentials).
     pub fn from_env() -> Result<Credentials, Box<dyn Error>> {
         let user_id = env::var("retarus_userid")?;
-        let password = env::var("retarus_password")?;
-        Ok(Credentials{username: user_id, password})
+        let password = Zeroizing::new(env::var("retarus_password")?);
+        Ok(Credentials::new(&user_id, &password))
+    }
+
+    /// True for the values of [Credentials::default].
+    pub fn is_placeholder(&self) -> bool {
+        self.username() == PLACEHOLDER_USERNAME && self.secret.expose() == PLACEHOLDER_PASSWORD
     }
 
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    pub fn password(&self) -> &Secret {
+        &self.secret
+    }
+
+    /// The value of the Authorization header, `Basic` followed by the encoded username and password.
+    pub fn authorization(&self) -> &Secret {
+        &self.authorization
+    }
+
+    /// The encoded username and password, without the `Basic` prefix of the Authorization header.
+    #[deprecated(note = "use Credentials::authorization, it is computed once and not copied")]
     pub fn encode(self) -> String {
-        let to_encode = format!("{}:{}", self.username, self.password);
-        base64::encode(to_encode)
+        encode(self.username(), self.secret.expose()).to_string()
     }
+}
 
-}
\ No newline at end of file
+fn encode(username: &str, password: &str) -> Zeroizing<String> {
+    let to_encode = Zeroizing::new(format!("{}:{}", username, password));
+    Zeroizing::new(base64::encode(to_encode.as_bytes()))
+}
+
+/// The password is never printed, clients and their transport are often logged with `{:?}`.
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Credentials").field("username", &self.username()).field("password", &self.secret).finish()
+    }
+}
+
+#[test]
+fn test_secret_is_redacted() {
+    let credentials = Credentials::new("user", "password");
+    assert_eq!(credentials.password().to_string(), "***");
+    assert!(!format!("{:?}", credentials).contains("password\""));
+    assert_eq!(credentials.password().expose(), "password");
+    assert_eq!(credentials.authorization().expose(), "Basic dXNlcjpwYXNzd29yZA==");
+    assert_eq!(credentials.username(), "user");
+    #[allow(deprecated)]
+    {
+        assert_eq!(credentials.encode(), "dXNlcjpwYXNzd29yZA==");
+    }
+}
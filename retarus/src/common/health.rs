@@ -0,0 +1,180 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use super::{transport::server, uri::RegionUri};
+
+/// Weight of the latest call in the average latency of a server.
+const LATENCY_WEIGHT: f64 = 0.3;
+
+/// The state of the circuit breaker of a server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// The server answers, it is asked in the order of its latency.
+    Closed,
+    /// The server failed too often, it is skipped until the open duration of the [TransportConfig](super::transport::TransportConfig) is over.
+    Open,
+    /// The open duration is over, the next call probes the server first and closes the circuit if it answers.
+    HalfOpen,
+}
+
+/// The health of a server of the region, returned by the server_health function of the clients.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerHealth {
+    pub server: String,
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    /// The average latency of the successful calls, None until the server answered once.
+    pub latency: Option<Duration>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    health: ServerHealth,
+    /// The scheme and host that the transporter reports the calls for.
+    key: String,
+    /// When the circuit was opened or the probe was started.
+    since: Instant,
+    /// Only the servers of the region are asked in turn, the high availability address is only observed.
+    failover: bool,
+}
+
+/// Tracks the servers of a client, a server is skipped after consecutive failures and probed again after a while.
+#[derive(Debug)]
+pub(crate) struct Health {
+    failure_threshold: u32,
+    open_duration: Duration,
+    entries: Mutex<Vec<Entry>>,
+}
+
+impl Health {
+    pub(crate) fn new(region_uri: &RegionUri, failure_threshold: u32, open_duration: Duration) -> Health {
+        let mut entries: Vec<Entry> = vec![];
+        let ha_addr = Some(&region_uri.ha_addr).filter(|addr| !addr.is_empty() && !region_uri.servers.contains(addr));
+        for (addr, failover) in ha_addr.map(|addr| (addr, false)).into_iter().chain(region_uri.servers.iter().map(|s| (s, true))) {
+            if entries.iter().any(|e| &e.health.server == addr) {
+                continue;
+            }
+            entries.push(Entry {
+                health: ServerHealth { server: addr.clone(), state: CircuitState::Closed, consecutive_failures: 0, latency: None },
+                key: server(addr),
+                since: Instant::now(),
+                failover,
+            });
+        }
+        Health { failure_threshold: failure_threshold.max(1), open_duration, entries: Mutex::new(entries) }
+    }
+
+    pub(crate) fn snapshot(&self) -> Vec<ServerHealth> {
+        self.entries.lock().unwrap().iter().map(|e| e.health.clone()).collect()
+    }
+
+    /// The servers of the region in the order they should be asked: a server that is probed first, then the servers
+    /// without failures by their latency and then the others. Open circuits are skipped, unless all circuits are open.
+    pub(crate) fn servers(&self) -> Vec<String> {
+        let mut entries = self.entries.lock().unwrap();
+        let now = Instant::now();
+        let mut probe = None;
+        for entry in entries.iter_mut().filter(|e| e.failover) {
+            let state = entry.health.state;
+            let expired = now.duration_since(entry.since) >= self.open_duration;
+            // a probe that never finished, e.g. because the caller stopped at another server, is started again
+            if probe.is_none() && state != CircuitState::Closed && expired {
+                entry.health.state = CircuitState::HalfOpen;
+                entry.since = now;
+                probe = Some(entry.health.server.clone());
+            }
+        }
+        let mut available: Vec<&Entry> = entries.iter().filter(|e| e.failover && e.health.state == CircuitState::Closed).collect();
+        available.sort_by_key(|e| (e.health.consecutive_failures > 0, e.health.latency.unwrap_or_default()));
+        let mut servers: Vec<String> = probe.into_iter().chain(available.iter().map(|e| e.health.server.clone())).collect();
+        if servers.is_empty() {
+            servers = entries.iter().filter(|e| e.failover).map(|e| e.health.server.clone()).collect();
+        }
+        servers
+    }
+
+    /// All servers of the region, regardless of their circuit.
+    pub(crate) fn region_servers(&self) -> Vec<String> {
+        self.entries.lock().unwrap().iter().filter(|e| e.failover).map(|e| e.health.server.clone()).collect()
+    }
+
+    /// The servers of the region that are missing in the servers to ask, because their circuit is open.
+    pub(crate) fn skipped(&self, servers: &[String]) -> Vec<String> {
+        let entries = self.entries.lock().unwrap();
+        entries.iter().filter(|e| e.failover && !servers.contains(&e.health.server)).map(|e| e.health.server.clone()).collect()
+    }
+
+    /// Record the result of a call to the server, identified by its scheme and host.
+    /// A server that answered without an error of the server closes its circuit.
+    pub(crate) fn record(&self, server: &str, success: bool, latency: Duration) {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = match entries.iter_mut().find(|e| e.key == server) {
+            Some(entry) => entry,
+            None => return,
+        };
+        let health = &mut entry.health;
+        if success {
+            health.latency = Some(match health.latency {
+                Some(average) => average.mul_f64(1.0 - LATENCY_WEIGHT) + latency.mul_f64(LATENCY_WEIGHT),
+                None => latency,
+            });
+            if health.state != CircuitState::Closed {
+                tracing::info!(server = %health.server, "the server answers again, the circuit is closed");
+            }
+            health.consecutive_failures = 0;
+            health.state = CircuitState::Closed;
+            return;
+        }
+        health.consecutive_failures += 1;
+        let open = health.state == CircuitState::HalfOpen || health.consecutive_failures >= self.failure_threshold;
+        if open && health.state != CircuitState::Open {
+            tracing::warn!(server = %health.server, failures = health.consecutive_failures, "the server is skipped, the circuit is open");
+        }
+        if open {
+            health.state = CircuitState::Open;
+            entry.since = Instant::now();
+        }
+    }
+}
+
+#[test]
+fn test_circuit_breaker() {
+    let region = RegionUri::new(super::uri::Region::Europe, "https://ha.example.com", vec!["https://a.example.com", "https://b.example.com"]);
+    let health = Health::new(&region, 2, Duration::from_millis(50));
+    assert_eq!(health.servers(), vec!["https://a.example.com", "https://b.example.com"]);
+    assert_eq!(health.snapshot().len(), 3);
+
+    // the faster server is asked first, a server with failures last
+    health.record("https://a.example.com", true, Duration::from_millis(200));
+    health.record("https://b.example.com", true, Duration::from_millis(20));
+    assert_eq!(health.servers(), vec!["https://b.example.com", "https://a.example.com"]);
+    health.record("https://b.example.com", false, Duration::ZERO);
+    assert_eq!(health.servers(), vec!["https://a.example.com", "https://b.example.com"]);
+
+    health.record("https://b.example.com", false, Duration::ZERO);
+    assert_eq!(health.snapshot()[2].state, CircuitState::Open);
+    assert_eq!(health.servers(), vec!["https://a.example.com"]);
+
+    // after the open duration one call probes the server, a failed probe opens the circuit again
+    std::thread::sleep(Duration::from_millis(60));
+    assert_eq!(health.servers(), vec!["https://b.example.com", "https://a.example.com"]);
+    assert_eq!(health.servers(), vec!["https://a.example.com"]);
+    assert_eq!(health.skipped(&health.servers()), vec!["https://b.example.com"]);
+    health.record("https://b.example.com", false, Duration::ZERO);
+    assert_eq!(health.snapshot()[2].state, CircuitState::Open);
+    std::thread::sleep(Duration::from_millis(60));
+    assert_eq!(health.servers()[0], "https://b.example.com");
+    health.record("https://b.example.com", true, Duration::from_millis(30));
+    let b = &health.snapshot()[2];
+    assert_eq!((b.state, b.consecutive_failures), (CircuitState::Closed, 0));
+    assert_eq!(b.latency, Some(Duration::from_millis(20).mul_f64(0.7) + Duration::from_millis(30).mul_f64(0.3)));
+
+    // if all circuits are open the servers are still asked
+    for server in ["https://a.example.com", "https://b.example.com"] {
+        health.record(server, false, Duration::ZERO);
+        health.record(server, false, Duration::ZERO);
+    }
+    assert_eq!(health.servers(), vec!["https://a.example.com", "https://b.example.com"]);
+}
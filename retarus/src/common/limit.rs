@@ -0,0 +1,184 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use super::build_error::BuildError;
+
+/// Limits the requests of a client to the throughput of the contract, so the server does not throttle them.
+/// Requests over the limit wait until they can be sent instead of failing.
+///
+/// The limit applies per account: the fax client limits each customer number it calls, the sms and webexpress clients
+/// have no customer number and limit each username of their credentials. Clones of a client share the limit, e.g. for
+/// concurrent campaigns, while a client that is built separately has a limit of its own.
+///
+/// ## Example
+/// ```rust
+/// use retarus::common::{creds::Credentials, limit::RateLimit};
+/// use retarus::sms::client::SmsClient;
+///
+/// let client = SmsClient::builder()
+///     .set_credentials(Credentials::new("your_user_id", "your password"))
+///     .set_rate_limit(RateLimit::new(10.0, 20, 4))
+///     .build();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    /// How many requests are started per second on average.
+    pub requests_per_second: f64,
+    /// How many requests can be started at once after a pause, at least one.
+    pub burst: u32,
+    /// How many requests can wait for their response at the same time, at least one.
+    pub max_in_flight: usize,
+}
+impl RateLimit {
+    pub fn new(requests_per_second: f64, burst: u32, max_in_flight: usize) -> RateLimit {
+        RateLimit { requests_per_second, burst, max_in_flight }
+    }
+
+    /// The reason why the limit would block every request, None if it is valid.
+    pub(crate) fn invalid(&self) -> Option<&'static str> {
+        if !(self.requests_per_second > 0.0 && self.requests_per_second.is_finite()) {
+            Some("requests_per_second must be a positive number")
+        } else if self.burst == 0 {
+            Some("burst must be at least 1")
+        } else if self.max_in_flight == 0 {
+            Some("max_in_flight must be at least 1")
+        } else {
+            None
+        }
+    }
+}
+
+/// The limiters of a client, one for each account it sends requests for. Clones of the client share them.
+#[derive(Debug, Clone)]
+pub(crate) struct AccountLimiters {
+    limit: RateLimit,
+    limiters: Arc<Mutex<HashMap<String, Arc<Limiter>>>>,
+}
+impl AccountLimiters {
+    pub(crate) fn new(limit: RateLimit) -> AccountLimiters {
+        AccountLimiters { limit, limiters: Default::default() }
+    }
+
+    /// The limiter of the account, it is created for the first request of the account.
+    pub(crate) fn get(&self, account: &str) -> Arc<Limiter> {
+        let mut limiters = self.limiters.lock().unwrap();
+        limiters.entry(account.to_string()).or_insert_with(|| Arc::new(Limiter::new(self.limit))).clone()
+    }
+}
+
+/// The limiters of a client if a limit is set, fails if the limit would block every request.
+pub(crate) fn select_limiter(service: &'static str, limit: Option<RateLimit>) -> Result<Option<AccountLimiters>, BuildError> {
+    let limit = match limit {
+        Some(limit) => limit,
+        None => return Ok(None),
+    };
+    if let Some(reason) = limit.invalid() {
+        return Err(BuildError::InvalidRateLimit { service, reason });
+    }
+    Ok(Some(AccountLimiters::new(limit)))
+}
+
+/// A token bucket for the rate and a semaphore for the requests in flight.
+#[derive(Debug)]
+pub(crate) struct Limiter {
+    limit: RateLimit,
+    in_flight: Arc<Semaphore>,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Negative if requests reserved tokens that are not yet refilled, they wait until then.
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Limiter {
+    pub(crate) fn new(limit: RateLimit) -> Limiter {
+        Limiter {
+            limit,
+            in_flight: Arc::new(Semaphore::new(limit.max_in_flight)),
+            bucket: Mutex::new(Bucket { tokens: limit.burst as f64, updated: Instant::now() }),
+        }
+    }
+
+    /// Waits for a free slot and a token, the slot is released when the permit is dropped.
+    /// Tokens are reserved in the order of the calls, so waiting requests are sent in order.
+    /// If the call is cancelled while it waits, its token is returned to the bucket.
+    pub(crate) async fn acquire(&self) -> OwnedSemaphorePermit {
+        let permit = self.in_flight.clone().acquire_owned().await.expect("the semaphore is never closed");
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let now = Instant::now();
+            let refilled = now.duration_since(bucket.updated).as_secs_f64() * self.limit.requests_per_second;
+            bucket.tokens = (bucket.tokens + refilled).min(self.limit.burst as f64) - 1.0;
+            bucket.updated = now;
+            if bucket.tokens < 0.0 {
+                Some(Duration::from_secs_f64(-bucket.tokens / self.limit.requests_per_second))
+            } else {
+                None
+            }
+        };
+        if let Some(wait) = wait {
+            tracing::debug!(wait_ms = wait.as_millis() as u64, "the rate limit is reached, the request waits");
+            let reservation = Reservation(&self.bucket, self.limit.burst as f64);
+            tokio::time::sleep(wait).await;
+            std::mem::forget(reservation);
+        }
+        permit
+    }
+}
+
+/// Returns the token of a request that was cancelled while it waited for the refill.
+struct Reservation<'a>(&'a Mutex<Bucket>, f64);
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        let mut bucket = self.0.lock().unwrap();
+        bucket.tokens = (bucket.tokens + 1.0).min(self.1);
+    }
+}
+
+#[tokio::test]
+async fn test_limiter() {
+    assert_eq!(RateLimit::new(0.0, 1, 1).invalid(), Some("requests_per_second must be a positive number"));
+    assert_eq!(RateLimit::new(1.0, 1, 0).invalid(), Some("max_in_flight must be at least 1"));
+
+    // the burst is sent at once, the next requests wait for the refill of the bucket
+    let limiter = Limiter::new(RateLimit::new(20.0, 2, 10));
+    let started = Instant::now();
+    for _ in 0..2 {
+        drop(limiter.acquire().await);
+    }
+    assert!(started.elapsed() < Duration::from_millis(40));
+    for _ in 0..2 {
+        drop(limiter.acquire().await);
+    }
+    assert!(started.elapsed() >= Duration::from_millis(95));
+
+    // a request waits until another one is finished
+    let limiter = Limiter::new(RateLimit::new(1000.0, 10, 1));
+    let permit = limiter.acquire().await;
+    assert!(tokio::time::timeout(Duration::from_millis(20), limiter.acquire()).await.is_err());
+    drop(permit);
+    assert!(tokio::time::timeout(Duration::from_millis(20), limiter.acquire()).await.is_ok());
+
+    // a cancelled request returns its token, the next one waits only for one refill
+    let limiter = Limiter::new(RateLimit::new(10.0, 1, 10));
+    drop(limiter.acquire().await);
+    assert!(tokio::time::timeout(Duration::from_millis(10), limiter.acquire()).await.is_err());
+    assert!(tokio::time::timeout(Duration::from_millis(150), limiter.acquire()).await.is_ok());
+
+    assert_eq!(select_limiter("fax", Some(RateLimit::new(1.0, 0, 1))).err(), Some(BuildError::InvalidRateLimit { service: "fax", reason: "burst must be at least 1" }));
+    let limiters = select_limiter("fax", Some(RateLimit::new(5.0, 1, 1))).unwrap().unwrap();
+    assert!(Arc::ptr_eq(&limiters.get("12345"), &limiters.clone().get("12345")));
+    assert!(!Arc::ptr_eq(&limiters.get("12345"), &limiters.get("54321")));
+    let other = select_limiter("fax", Some(RateLimit::new(6.0, 1, 1))).unwrap().unwrap();
+    assert!(!Arc::ptr_eq(&limiters.get("12345"), &other.get("12345")));
+}
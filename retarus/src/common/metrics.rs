@@ -0,0 +1,77 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+/// Receives the measurements of the clients, e.g. to export them to a monitoring system. All methods do nothing by default,
+/// so an implementation only needs to handle the measurements it is interested in. With the `prometheus` feature
+/// [PrometheusMetrics](super::prometheus::PrometheusMetrics) collects all of them.
+///
+/// The service is "fax", "sms" or "webexpress". Servers are only the scheme and host, paths contain customer numbers and job ids.
+pub trait Metrics: fmt::Debug + Send + Sync {
+    /// A job or list was accepted by the service.
+    fn job_submitted(&self, _service: &'static str) {}
+
+    /// A call failed, counted once per call and not for every attempt.
+    fn call_failed(&self, _service: &'static str, _kind: FailureKind) {}
+
+    /// An http attempt finished, the status is None if no response was received.
+    fn http_request(&self, _service: &'static str, _server: &str, _status: Option<u16>, _latency: Duration) {}
+
+    /// The server did not know the job or could not answer, the next server of the region is asked.
+    fn failover(&self, _service: &'static str, _server: &str) {}
+
+    /// The status of a fax recipient in a fetched report, recipients that are still processed are not counted.
+    /// A report that is fetched again is counted again.
+    fn fax_recipient(&self, _status: &str, _reason: &str) {}
+}
+
+/// Why a call failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FailureKind {
+    /// No connection to the server could be established, after all retries.
+    Connect,
+    Timeout,
+    /// Any other error of the http connection.
+    Http,
+    /// The credentials could not be loaded by the provider.
+    Credentials,
+    /// The server answered, but refused the job.
+    Rejected,
+}
+impl FailureKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FailureKind::Connect => "connect",
+            FailureKind::Timeout => "timeout",
+            FailureKind::Http => "http",
+            FailureKind::Credentials => "credentials",
+            FailureKind::Rejected => "rejected",
+        }
+    }
+}
+
+/// Used if no metrics are set, drops all measurements.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoMetrics;
+impl Metrics for NoMetrics {}
+
+/// Allows to keep a reference to the metrics, e.g. to export them, and to share them between clients.
+impl<M: Metrics + ?Sized> Metrics for Arc<M> {
+    fn job_submitted(&self, service: &'static str) {
+        (**self).job_submitted(service)
+    }
+
+    fn call_failed(&self, service: &'static str, kind: FailureKind) {
+        (**self).call_failed(service, kind)
+    }
+
+    fn http_request(&self, service: &'static str, server: &str, status: Option<u16>, latency: Duration) {
+        (**self).http_request(service, server, status, latency)
+    }
+
+    fn failover(&self, service: &'static str, server: &str) {
+        (**self).failover(service, server)
+    }
+
+    fn fax_recipient(&self, status: &str, reason: &str) {
+        (**self).fax_recipient(status, reason)
+    }
+}
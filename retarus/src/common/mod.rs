@@ -1,3 +1,16 @@
 pub mod transport;
 pub mod creds;
-pub mod uri;
\ No newline at end of file
+pub mod uri;
+pub mod phone;
+pub mod schedule;
+pub mod suppression;
+pub mod provider;
+#[cfg(feature = "config")]
+pub mod config;
+pub mod build_error;
+pub mod metrics;
+pub mod limit;
+pub mod health;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+pub(crate) mod runtime;
\ No newline at end of file
@@ -0,0 +1,290 @@
+use std::{fmt, str::FromStr};
+
+/// Assigned country calling codes with three digits, one and two digit codes are checked in [country_code_len].
+const THREE_DIGIT_CODES: &[u16] = &[
+    211, 212, 213, 216, 218, 220, 221, 222, 223, 224, 225, 226, 227, 228, 229, 230, 231, 232, 233, 234, 235, 236,
+    237, 238, 239, 240, 241, 242, 243, 244, 245, 246, 247, 248, 249, 250, 251, 252, 253, 254, 255, 256, 257, 258,
+    260, 261, 262, 263, 264, 265, 266, 267, 268, 269, 290, 291, 297, 298, 299, 350, 351, 352, 353, 354, 355, 356,
+    357, 358, 359, 370, 371, 372, 373, 374, 375, 376, 377, 378, 379, 380, 381, 382, 383, 385, 386, 387, 389, 420,
+    421, 423, 500, 501, 502, 503, 504, 505, 506, 507, 508, 509, 590, 591, 592, 593, 594, 595, 596, 597, 598, 599,
+    670, 672, 673, 674, 675, 676, 677, 678, 679, 680, 681, 682, 683, 685, 686, 687, 688, 689, 690, 691, 692, 800,
+    808, 850, 852, 853, 855, 856, 870, 878, 880, 881, 882, 883, 886, 888, 960, 961, 962, 963, 964, 965, 966, 967,
+    968, 970, 971, 972, 973, 974, 975, 976, 977, 979, 992, 993, 994, 995, 996, 998,
+];
+const TWO_DIGIT_CODES: &[u16] = &[
+    20, 27, 30, 31, 32, 33, 34, 36, 39, 40, 41, 43, 44, 45, 46, 47, 48, 49, 51, 52, 53, 54, 55, 56, 57, 58, 60,
+    61, 62, 63, 64, 65, 66, 81, 82, 84, 86, 90, 91, 92, 93, 94, 95, 98,
+];
+/// E.164 allows at most 15 digits including the country code.
+const MAX_DIGITS: usize = 15;
+/// Shortest national number that is accepted.
+const MIN_NATIONAL_DIGITS: usize = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PhoneNumberError {
+    Empty,
+    InvalidCharacter(char),
+    MissingCountryCode,
+    UnknownCountryCode,
+    TooShort,
+    TooLong,
+}
+impl fmt::Display for PhoneNumberError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PhoneNumberError::Empty => write!(f, "The phone number is empty"),
+            PhoneNumberError::InvalidCharacter(c) => write!(f, "The phone number contains the invalid character '{}'", c),
+            PhoneNumberError::MissingCountryCode => write!(f, "The phone number is in national format, but no default country code was given"),
+            PhoneNumberError::UnknownCountryCode => write!(f, "The phone number does not start with a known country code"),
+            PhoneNumberError::TooShort => write!(f, "The phone number is too short"),
+            PhoneNumberError::TooLong => write!(f, "The phone number has more than {} digits", MAX_DIGITS),
+        }
+    }
+}
+
+impl std::error::Error for PhoneNumberError {}
+
+/// A phone number normalized to the E.164 format, can be used as recipient for fax and sms jobs.
+///
+/// ## Example
+/// ```rust
+/// use retarus::common::phone::{CountryCode, PhoneNumber};
+///
+/// let number = PhoneNumber::parse("089 / 5528-0", Some(CountryCode::new(49).unwrap())).unwrap();
+/// assert_eq!(number.to_string(), "+498955280");
+/// assert_eq!(number.country_code(), 49);
+/// assert_eq!(PhoneNumber::parse("0049 89 55280", None).unwrap(), number);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PhoneNumber {
+    country_code: u16,
+    national_number: String,
+}
+impl PhoneNumber {
+    /// Parse a number in international (`+49...`, `0049...`) or national (`089...`) format.
+    ///
+    /// Without a default_country a number without `+` or `00` has to start with the country code, e.g. `4989...`.
+    /// With a default_country every such number is national: its trunk prefix is removed and the country code is added,
+    /// e.g. `089...` for Germany, `06...` for Italy where the 0 is part of the number, or `212...` and `1212...` for the NANP.
+    pub fn parse(input: &str, default_country: Option<CountryCode>) -> Result<PhoneNumber, PhoneNumberError> {
+        let trimmed = input.trim();
+        let (international, rest) = match trimmed.strip_prefix('+') {
+            Some(rest) => (true, rest),
+            None => (false, trimmed),
+        };
+        let mut digits = String::with_capacity(rest.len());
+        for c in rest.chars() {
+            match c {
+                '0'..='9' => digits.push(c),
+                ' ' | '-' | '.' | '/' | '(' | ')' => {}
+                _ => return Err(PhoneNumberError::InvalidCharacter(c)),
+            }
+        }
+        if digits.is_empty() {
+            return Err(PhoneNumberError::Empty);
+        }
+
+        let full = if international {
+            digits
+        } else if let Some(rest) = digits.strip_prefix("00") {
+            rest.to_string()
+        } else if let Some(country) = default_country {
+            let national = country.trunk_prefix().and_then(|prefix| digits.strip_prefix(prefix)).unwrap_or(&digits);
+            format!("{}{}", country.code(), national)
+        } else if digits.starts_with('0') {
+            return Err(PhoneNumberError::MissingCountryCode);
+        } else {
+            digits
+        };
+
+        let len = country_code_len(&full).ok_or(PhoneNumberError::UnknownCountryCode)?;
+        if full.len() > MAX_DIGITS {
+            return Err(PhoneNumberError::TooLong);
+        }
+        if full.len() - len < MIN_NATIONAL_DIGITS {
+            return Err(PhoneNumberError::TooShort);
+        }
+        Ok(PhoneNumber {
+            country_code: full[..len].parse().unwrap(),
+            national_number: full[len..].to_string(),
+        })
+    }
+
+    pub fn country_code(&self) -> u16 {
+        self.country_code
+    }
+
+    /// The number without the country code and without the trunk prefix.
+    pub fn national_number(&self) -> &str {
+        &self.national_number
+    }
+
+    /// The number in E.164 format, e.g. `+498955280`.
+    pub fn e164(&self) -> String {
+        self.to_string()
+    }
+}
+impl fmt::Display for PhoneNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "+{}{}", self.country_code, self.national_number)
+    }
+}
+impl FromStr for PhoneNumber {
+    type Err = PhoneNumberError;
+
+    /// Parse an international number, use [PhoneNumber::parse] for national numbers.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PhoneNumber::parse(s, None)
+    }
+}
+
+/// A country calling code, e.g. `+49` for Germany.
+///
+/// ## Example
+/// ```rust
+/// use retarus::common::phone::CountryCode;
+///
+/// let code: CountryCode = "0049".parse().unwrap();
+/// assert_eq!(code, CountryCode::new(49).unwrap());
+/// assert_eq!(code.to_string(), "+49");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CountryCode(u16);
+impl CountryCode {
+    pub fn new(code: u16) -> Result<CountryCode, PhoneNumberError> {
+        let digits = code.to_string();
+        if country_code_len(&digits) != Some(digits.len()) {
+            return Err(PhoneNumberError::UnknownCountryCode);
+        }
+        Ok(CountryCode(code))
+    }
+
+    pub fn code(&self) -> u16 {
+        self.0
+    }
+
+    /// The prefix of national numbers that is not part of the number, e.g. `0` in Germany or `1` in the NANP.
+    /// Italy, San Marino and the Vatican have none, the leading 0 of their numbers is kept.
+    fn trunk_prefix(&self) -> Option<&'static str> {
+        match self.0 {
+            1 => Some("1"),
+            7 => Some("8"),
+            36 => Some("06"),
+            39 | 378 | 379 => None,
+            _ => Some("0"),
+        }
+    }
+}
+impl fmt::Display for CountryCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "+{}", self.0)
+    }
+}
+impl FromStr for CountryCode {
+    type Err = PhoneNumberError;
+
+    /// Accepts `+49`, `0049` and `49`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let digits = s.strip_prefix('+').or_else(|| s.strip_prefix("00")).unwrap_or(s);
+        if digits.is_empty() {
+            return Err(PhoneNumberError::Empty);
+        }
+        if let Some(c) = digits.chars().find(|c| !c.is_ascii_digit()) {
+            return Err(PhoneNumberError::InvalidCharacter(c));
+        }
+        let code = digits.parse().map_err(|_| PhoneNumberError::UnknownCountryCode)?;
+        CountryCode::new(code)
+    }
+}
+impl From<CountryCode> for u16 {
+    fn from(code: CountryCode) -> u16 {
+        code.0
+    }
+}
+
+/// Country calling codes are prefix free, so at most one of the prefixes is an assigned code.
+fn country_code_len(digits: &str) -> Option<usize> {
+    if digits.starts_with('0') {
+        return None;
+    }
+    if digits.starts_with('1') || digits.starts_with('7') {
+        return Some(1);
+    }
+    let prefix = |len: usize| digits.get(..len).and_then(|p| p.parse::<u16>().ok());
+    if prefix(2).is_some_and(|code| TWO_DIGIT_CODES.contains(&code)) {
+        return Some(2);
+    }
+    if prefix(3).is_some_and(|code| THREE_DIGIT_CODES.contains(&code)) {
+        return Some(3);
+    }
+    None
+}
+
+/// A number for logs, only the first three and the last two characters are kept, e.g. `+49*********00`.
+pub fn redact(number: &str) -> String {
+    let chars: Vec<char> = number.chars().collect();
+    if chars.len() <= 5 {
+        return "*".repeat(chars.len());
+    }
+    let hidden = "*".repeat(chars.len() - 5);
+    format!("{}{}{}", chars[..3].iter().collect::<String>(), hidden, chars[chars.len() - 2..].iter().collect::<String>())
+}
+
+#[test]
+fn test_parse_formats() {
+    let expected = "+4912310000000";
+    let germany = CountryCode::new(49).ok();
+    for input in ["+49 123 10000000", "0049-123-10000000", "0123 / 10000000", "123 10000000", "+49 (123) 10000000"] {
+        assert_eq!(PhoneNumber::parse(input, germany).unwrap().e164(), expected, "{}", input);
+    }
+    assert_eq!(PhoneNumber::parse("4912310000000", None).unwrap().e164(), expected);
+    let us = PhoneNumber::parse("+1 212 555 0100", None).unwrap();
+    assert_eq!(us.country_code(), 1);
+    assert_eq!(us.national_number(), "2125550100");
+    assert_eq!(PhoneNumber::parse("+352 4711", None).unwrap().country_code(), 352);
+}
+
+#[test]
+fn test_parse_national_italy() {
+    let italy = CountryCode::new(39).ok();
+    let number = PhoneNumber::parse("06 6988 3145", italy).unwrap();
+    assert_eq!(number.e164(), "+390669883145");
+    assert_eq!(number.national_number(), "0669883145");
+    assert_eq!(PhoneNumber::parse("+39 06 6988 3145", None).unwrap(), number);
+    assert_eq!(PhoneNumber::parse("0039 06 6988 3145", italy).unwrap(), number);
+}
+
+#[test]
+fn test_parse_national_nanp() {
+    let nanp = CountryCode::new(1).ok();
+    for input in ["9369698995", "1 936 969 8995", "(936) 969-8995", "+1 936 969 8995"] {
+        assert_eq!(PhoneNumber::parse(input, nanp).unwrap().e164(), "+19369698995", "{}", input);
+    }
+}
+
+#[test]
+fn test_country_code() {
+    assert_eq!("+41".parse::<CountryCode>().unwrap().code(), 41);
+    assert_eq!("1".parse::<CountryCode>().unwrap().code(), 1);
+    assert_eq!("+352".parse::<CountryCode>().unwrap().code(), 352);
+    assert_eq!("+4".parse::<CountryCode>(), Err(PhoneNumberError::UnknownCountryCode));
+    assert_eq!("+491".parse::<CountryCode>(), Err(PhoneNumberError::UnknownCountryCode));
+    assert_eq!("+4a".parse::<CountryCode>(), Err(PhoneNumberError::InvalidCharacter('a')));
+}
+
+#[test]
+fn test_parse_invalid() {
+    assert_eq!(PhoneNumber::parse("", None), Err(PhoneNumberError::Empty));
+    assert_eq!(PhoneNumber::parse("089 55280", None), Err(PhoneNumberError::MissingCountryCode));
+    assert_eq!(PhoneNumber::parse("+49 89 5528a", None), Err(PhoneNumberError::InvalidCharacter('a')));
+    assert_eq!(PhoneNumber::parse("+28 123456", None), Err(PhoneNumberError::UnknownCountryCode));
+    assert_eq!(PhoneNumber::parse("+49 12", None), Err(PhoneNumberError::TooShort));
+    assert_eq!(PhoneNumber::parse("+49 1234567890123456", None), Err(PhoneNumberError::TooLong));
+}
+
+#[test]
+fn test_redact() {
+    assert_eq!(redact("+4912310000000"), "+49*********00");
+    assert_eq!(redact("+4912"), "*****");
+}
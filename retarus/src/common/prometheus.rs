@@ -0,0 +1,165 @@
+use std::{collections::BTreeMap, convert::Infallible, fmt::Write, net::SocketAddr, sync::{Arc, Mutex}, time::Duration};
+
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+
+use super::metrics::{FailureKind, Metrics};
+
+/// Upper bounds of the latency histogram in seconds.
+const LATENCY_BUCKETS: [f64; 10] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+/// Collects the [Metrics] of the clients and renders them in the Prometheus text format.
+///
+/// ## Example
+/// ```rust
+/// use std::sync::Arc;
+/// use retarus::common::{creds::Credentials, prometheus::PrometheusMetrics};
+/// use retarus::fax::client::ClientSDK;
+///
+/// let metrics = Arc::new(PrometheusMetrics::new());
+/// let client = ClientSDK::builder()
+///     .set_credentials(Credentials::new("your_user_id", "your password"))
+///     .set_customer_number("customer_number".to_string())
+///     .set_metrics(metrics.clone())
+///     .build();
+/// // tokio::spawn(retarus::common::prometheus::serve(metrics, ([127, 0, 0, 1], 9184).into()));
+/// ```
+#[derive(Debug, Default)]
+pub struct PrometheusMetrics {
+    /// Keyed by the name of the metric and the rendered labels.
+    counters: Mutex<BTreeMap<(&'static str, String), u64>>,
+    latencies: Mutex<BTreeMap<String, Histogram>>,
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Not cumulative, the count of each bucket of [LATENCY_BUCKETS] and the count above the last bucket.
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl PrometheusMetrics {
+    pub fn new() -> PrometheusMetrics {
+        PrometheusMetrics::default()
+    }
+
+    fn increment(&self, name: &'static str, labels: &[(&str, &str)]) {
+        *self.counters.lock().unwrap().entry((name, render_labels(labels))).or_insert(0) += 1;
+    }
+
+    /// All metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut last = "";
+        for ((name, labels), value) in self.counters.lock().unwrap().iter() {
+            if *name != last {
+                let _ = writeln!(out, "# TYPE {} counter", name);
+                last = name;
+            }
+            let _ = writeln!(out, "{}{} {}", name, labels, value);
+        }
+        let latencies = self.latencies.lock().unwrap();
+        if !latencies.is_empty() {
+            let _ = writeln!(out, "# TYPE retarus_http_request_duration_seconds histogram");
+        }
+        for (labels, histogram) in latencies.iter() {
+            let labels = labels.trim_start_matches('{').trim_end_matches('}');
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().map(|b| b.to_string()).chain(["+Inf".to_string()]).zip(histogram.buckets) {
+                cumulative += count;
+                let _ = writeln!(out, "retarus_http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}", labels, bound, cumulative);
+            }
+            let _ = writeln!(out, "retarus_http_request_duration_seconds_sum{{{}}} {}", labels, histogram.sum);
+            let _ = writeln!(out, "retarus_http_request_duration_seconds_count{{{}}} {}", labels, histogram.count);
+        }
+        out
+    }
+}
+
+impl Metrics for PrometheusMetrics {
+    fn job_submitted(&self, service: &'static str) {
+        self.increment("retarus_jobs_submitted_total", &[("service", service)]);
+    }
+
+    fn call_failed(&self, service: &'static str, kind: FailureKind) {
+        self.increment("retarus_call_failures_total", &[("service", service), ("kind", kind.as_str())]);
+    }
+
+    fn http_request(&self, service: &'static str, server: &str, status: Option<u16>, latency: Duration) {
+        let status = status.map(|s| s.to_string()).unwrap_or_else(|| "none".to_string());
+        self.increment("retarus_http_requests_total", &[("service", service), ("server", server), ("status", &status)]);
+        let seconds = latency.as_secs_f64();
+        let mut latencies = self.latencies.lock().unwrap();
+        let histogram = latencies.entry(render_labels(&[("service", service), ("server", server)])).or_default();
+        let bucket = LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound).unwrap_or(LATENCY_BUCKETS.len());
+        histogram.buckets[bucket] += 1;
+        histogram.sum += seconds;
+        histogram.count += 1;
+    }
+
+    fn failover(&self, service: &'static str, server: &str) {
+        self.increment("retarus_failovers_total", &[("service", service), ("server", server)]);
+    }
+
+    fn fax_recipient(&self, status: &str, reason: &str) {
+        self.increment("retarus_fax_recipients_total", &[("status", status), ("reason", reason)]);
+    }
+}
+
+fn render_labels(labels: &[(&str, &str)]) -> String {
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")))
+        .collect();
+    format!("{{{}}}", labels.join(","))
+}
+
+/// Serve the metrics on `/metrics` until the future is dropped, e.g. on 127.0.0.1:9184 for a local Prometheus agent.
+/// Fails if the address cannot be bound, e.g. because it is already in use.
+pub async fn serve(metrics: Arc<PrometheusMetrics>, addr: SocketAddr) -> Result<(), hyper::Error> {
+    let make_service = make_service_fn(move |_| {
+        let metrics = metrics.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                let metrics = metrics.clone();
+                async move {
+                    let response = match req.uri().path() {
+                        "/metrics" => Response::builder()
+                            .header("Content-Type", "text/plain; version=0.0.4")
+                            .body(Body::from(metrics.render())),
+                        _ => Response::builder().status(StatusCode::NOT_FOUND).body(Body::empty()),
+                    };
+                    Ok::<_, Infallible>(response.expect("the response is valid"))
+                }
+            }))
+        }
+    });
+    Server::try_bind(&addr)?.serve(make_service).await
+}
+
+#[test]
+fn test_render_metrics() {
+    let metrics = PrometheusMetrics::new();
+    metrics.job_submitted("fax");
+    metrics.job_submitted("fax");
+    metrics.call_failed("sms", FailureKind::Timeout);
+    metrics.http_request("fax", "https://faxws.de1.retarus.com", Some(200), Duration::from_millis(300));
+    metrics.fax_recipient("BUSY", "line \"busy\"");
+    let text = metrics.render();
+    assert!(text.contains("# TYPE retarus_jobs_submitted_total counter\nretarus_jobs_submitted_total{service=\"fax\"} 2\n"));
+    assert!(text.contains("retarus_call_failures_total{service=\"sms\",kind=\"timeout\"} 1"));
+    assert!(text.contains("retarus_fax_recipients_total{status=\"BUSY\",reason=\"line \\\"busy\\\"\"} 1"));
+    let labels = "service=\"fax\",server=\"https://faxws.de1.retarus.com\"";
+    assert!(text.contains(&format!("retarus_http_request_duration_seconds_bucket{{{},le=\"0.25\"}} 0", labels)));
+    assert!(text.contains(&format!("retarus_http_request_duration_seconds_bucket{{{},le=\"0.5\"}} 1", labels)));
+    assert!(text.contains(&format!("retarus_http_request_duration_seconds_count{{{}}} 1", labels)));
+}
+
+#[tokio::test]
+async fn test_serve_on_used_address() {
+    let used = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    assert!(serve(Arc::new(PrometheusMetrics::new()), used.local_addr().unwrap()).await.is_err());
+}
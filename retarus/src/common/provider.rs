@@ -0,0 +1,301 @@
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use zeroize::Zeroizing;
+
+use super::creds::Credentials;
+
+/// Supplies the credentials of a client, the client asks the provider for every request.
+/// This allows to rotate passwords without restarting the program, see [ReloadableCredentials].
+///
+/// ## Example
+/// ```rust
+/// use retarus::common::provider::EnvCredentials;
+/// use retarus::fax::client::ClientSDK;
+///
+/// let client = ClientSDK::builder()
+///     .set_customer_number("customer_number".to_string())
+///     .set_credential_provider(EnvCredentials::new("retarus_userid", "retarus_fax_password"))
+///     .build();
+/// ```
+pub trait CredentialProvider: fmt::Debug + Send + Sync {
+    fn credentials(&self) -> Result<Arc<Credentials>, CredentialError>;
+
+    /// True if the next call of [credentials](CredentialProvider::credentials) may block the thread, e.g. to read a file.
+    /// The clients then ask the provider on a blocking thread of the runtime.
+    fn blocks(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CredentialError {
+    /// The environment variable is not set or not valid unicode.
+    MissingVar(String),
+    /// The file could not be read, contains the path and the reason.
+    Unreadable(String, String),
+    /// The variable or file of the username or password exists, but it is empty.
+    Empty(String),
+}
+impl fmt::Display for CredentialError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CredentialError::MissingVar(name) => write!(f, "The environment variable {} is not set", name),
+            CredentialError::Unreadable(path, reason) => write!(f, "The credentials could not be read from {}: {}", path, reason),
+            CredentialError::Empty(source) => write!(f, "The credentials from {} are empty", source),
+        }
+    }
+}
+
+impl std::error::Error for CredentialError {}
+
+/// Fixed credentials, used if a client is configured with set_credentials. Every request shares them instead of a copy.
+#[derive(Debug, Clone)]
+pub struct StaticCredentials(Arc<Credentials>);
+impl From<Credentials> for StaticCredentials {
+    fn from(credentials: Credentials) -> Self {
+        StaticCredentials(Arc::new(credentials))
+    }
+}
+impl CredentialProvider for StaticCredentials {
+    fn credentials(&self) -> Result<Arc<Credentials>, CredentialError> {
+        Ok(self.0.clone())
+    }
+}
+
+/// Allows to share a provider, e.g. a [ReloadableCredentials] that is reloaded by another part of the program.
+impl<P: CredentialProvider + ?Sized> CredentialProvider for Arc<P> {
+    fn credentials(&self) -> Result<Arc<Credentials>, CredentialError> {
+        (**self).credentials()
+    }
+
+    fn blocks(&self) -> bool {
+        (**self).blocks()
+    }
+}
+
+/// Reads the credentials from environment variables for every request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvCredentials {
+    username_var: String,
+    password_var: String,
+}
+impl EnvCredentials {
+    pub fn new(username_var: &str, password_var: &str) -> EnvCredentials {
+        EnvCredentials { username_var: username_var.to_string(), password_var: password_var.to_string() }
+    }
+
+    /// Reads the variables with the given lookup instead of the process environment.
+    pub(crate) fn read_with<F: Fn(&str) -> Option<String>>(&self, lookup: F) -> Result<Arc<Credentials>, CredentialError> {
+        let var = |name: &str| {
+            lookup(name)
+                .map(Zeroizing::new)
+                .ok_or_else(|| CredentialError::MissingVar(name.to_string()))
+                .and_then(|value| not_empty(value, name))
+        };
+        Ok(Arc::new(Credentials::new(&var(&self.username_var)?, &var(&self.password_var)?)))
+    }
+}
+
+/// Uses retarus_userid and retarus_password, the same variables as [Credentials::from_env].
+impl Default for EnvCredentials {
+    fn default() -> Self {
+        EnvCredentials::new("retarus_userid", "retarus_password")
+    }
+}
+
+impl CredentialProvider for EnvCredentials {
+    fn credentials(&self) -> Result<Arc<Credentials>, CredentialError> {
+        self.read_with(|name| env::var(name).ok())
+    }
+}
+
+/// Reads the username and password from two files for every request, e.g. from a docker or kubernetes secret mount.
+/// Surrounding whitespace and the trailing newline of the files are removed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileCredentials {
+    username_path: PathBuf,
+    password_path: PathBuf,
+}
+impl FileCredentials {
+    pub fn new<P: Into<PathBuf>>(username_path: P, password_path: P) -> FileCredentials {
+        FileCredentials { username_path: username_path.into(), password_path: password_path.into() }
+    }
+
+    /// A directory that contains the files `username` and `password`, the layout of a mounted kubernetes secret.
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> FileCredentials {
+        FileCredentials::new(dir.as_ref().join("username"), dir.as_ref().join("password"))
+    }
+}
+impl CredentialProvider for FileCredentials {
+    fn credentials(&self) -> Result<Arc<Credentials>, CredentialError> {
+        let read = |path: &Path| {
+            fs::read_to_string(path)
+                .map(|content| Zeroizing::new(Zeroizing::new(content).trim().to_string()))
+                .map_err(|err| CredentialError::Unreadable(path.display().to_string(), err.to_string()))
+                .and_then(|value| not_empty(value, &path.display().to_string()))
+        };
+        Ok(Arc::new(Credentials::new(&read(&self.username_path)?, &read(&self.password_path)?)))
+    }
+
+    fn blocks(&self) -> bool {
+        true
+    }
+}
+
+/// Caches the credentials of another provider until they are reloaded, either with [ReloadableCredentials::reload],
+/// e.g. on a signal, or automatically after the refresh interval. A failed automatic reload keeps the last credentials
+/// and is tried again after the next refresh interval, so a broken source is not read for every request.
+///
+/// ## Example
+/// ```rust
+/// use std::{sync::Arc, time::Duration};
+/// use retarus::common::provider::{FileCredentials, ReloadableCredentials};
+///
+/// let provider = Arc::new(
+///     ReloadableCredentials::new(FileCredentials::from_dir("/run/secrets/retarus"))
+///         .set_refresh_interval(Duration::from_secs(300)),
+/// );
+/// // pass a clone of the Arc to the client builders and call provider.reload() after the secret was rotated
+/// ```
+#[derive(Debug)]
+pub struct ReloadableCredentials {
+    source: Box<dyn CredentialProvider>,
+    cached: RwLock<Option<(Arc<Credentials>, Instant)>>,
+    refresh_interval: Option<Duration>,
+}
+impl ReloadableCredentials {
+    pub fn new<P: CredentialProvider + 'static>(source: P) -> ReloadableCredentials {
+        ReloadableCredentials { source: Box::new(source), cached: RwLock::new(None), refresh_interval: None }
+    }
+
+    pub fn set_refresh_interval(mut self, interval: Duration) -> ReloadableCredentials {
+        self.refresh_interval = Some(interval);
+        self
+    }
+
+    /// Read the credentials from the source again, the cached credentials are kept if this fails.
+    pub fn reload(&self) -> Result<(), CredentialError> {
+        let credentials = self.source.credentials()?;
+        *self.cached.write().unwrap() = Some((credentials, Instant::now()));
+        Ok(())
+    }
+
+    /// No credentials are cached yet or the refresh interval passed since the last load or failed attempt.
+    fn reload_due(&self) -> bool {
+        match self.cached.read().unwrap().as_ref() {
+            Some((_, loaded)) => self.refresh_interval.is_some_and(|interval| loaded.elapsed() >= interval),
+            None => true,
+        }
+    }
+}
+impl CredentialProvider for ReloadableCredentials {
+    fn blocks(&self) -> bool {
+        self.source.blocks() && self.reload_due()
+    }
+
+    fn credentials(&self) -> Result<Arc<Credentials>, CredentialError> {
+        let cached = self.cached.read().unwrap().clone();
+        match cached {
+            Some((credentials, loaded)) => {
+                let expired = self.refresh_interval.is_some_and(|interval| loaded.elapsed() >= interval);
+                if !expired {
+                    return Ok(credentials);
+                }
+                if let Err(err) = self.reload() {
+                    tracing::warn!(error = %err, "the credentials could not be reloaded, the last ones are used");
+                    // the failed attempt starts the next interval, unless another request reloaded them in the meantime
+                    if let Some((current, loaded)) = self.cached.write().unwrap().as_mut() {
+                        if Arc::ptr_eq(current, &credentials) {
+                            *loaded = Instant::now();
+                        }
+                    }
+                }
+                Ok(self.cached.read().unwrap().as_ref().expect("set before").0.clone())
+            }
+            None => {
+                self.reload()?;
+                Ok(self.cached.read().unwrap().as_ref().expect("set by reload").0.clone())
+            }
+        }
+    }
+}
+
+/// Reports the variable or file that is empty, so the error points to the broken part of the secret.
+fn not_empty(value: Zeroizing<String>, source: &str) -> Result<Zeroizing<String>, CredentialError> {
+    if value.is_empty() {
+        return Err(CredentialError::Empty(source.to_string()));
+    }
+    Ok(value)
+}
+
+#[test]
+fn test_env_credentials() {
+    let provider = EnvCredentials::new("user", "password");
+    let vars = |user: &'static str, password: Option<&'static str>| move |name: &str| match name {
+        "user" => Some(user.to_string()),
+        _ => password.map(str::to_string),
+    };
+    assert_eq!(provider.read_with(vars("user", None)).err(), Some(CredentialError::MissingVar("password".to_string())));
+    assert_eq!(*provider.read_with(vars("user", Some("secret"))).unwrap(), Credentials::new("user", "secret"));
+    assert_eq!(provider.read_with(vars("", Some("secret"))).err(), Some(CredentialError::Empty("user".to_string())));
+}
+
+#[test]
+fn test_file_and_reloadable_credentials() {
+    let dir = env::temp_dir().join(format!("retarus_secret_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("username"), "user\n").unwrap();
+    fs::write(dir.join("password"), "first\n").unwrap();
+
+    let reloadable = ReloadableCredentials::new(FileCredentials::from_dir(&dir));
+    assert_eq!(reloadable.credentials().unwrap().password().expose(), "first");
+    fs::write(dir.join("password"), "second").unwrap();
+    assert_eq!(reloadable.credentials().unwrap().password().expose(), "first");
+    reloadable.reload().unwrap();
+    assert_eq!(reloadable.credentials().unwrap().password().expose(), "second");
+
+    // a broken secret keeps the last credentials
+    fs::write(dir.join("password"), "").unwrap();
+    assert!(reloadable.reload().is_err());
+    let refreshing = reloadable.set_refresh_interval(Duration::ZERO);
+    assert_eq!(refreshing.credentials().unwrap().password().expose(), "second");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_failed_reload_waits_for_the_next_interval() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Debug, Default)]
+    struct Flaky(AtomicUsize);
+    impl CredentialProvider for Flaky {
+        fn credentials(&self) -> Result<Arc<Credentials>, CredentialError> {
+            match self.0.fetch_add(1, Ordering::SeqCst) {
+                0 => Ok(Arc::new(Credentials::new("user", "first"))),
+                _ => Err(CredentialError::Empty("password".to_string())),
+            }
+        }
+
+        fn blocks(&self) -> bool {
+            true
+        }
+    }
+
+    let source = Arc::new(Flaky::default());
+    let reloadable = ReloadableCredentials::new(source.clone()).set_refresh_interval(Duration::from_millis(200));
+    assert!(reloadable.blocks());
+    assert_eq!(reloadable.credentials().unwrap().password().expose(), "first");
+    assert!(!reloadable.blocks());
+    std::thread::sleep(Duration::from_millis(250));
+    assert!(reloadable.blocks());
+    assert_eq!(reloadable.credentials().unwrap().password().expose(), "first");
+    assert_eq!(reloadable.credentials().unwrap().password().expose(), "first");
+    assert_eq!(source.0.load(Ordering::SeqCst), 2);
+    assert!(!reloadable.blocks());
+    assert!(!EnvCredentials::default().blocks());
+}
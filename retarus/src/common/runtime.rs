@@ -0,0 +1,67 @@
+use std::{future::Future, io};
+
+use tokio::runtime::{Builder, Handle, Runtime, RuntimeFlavor};
+
+/// Drives the io of the sync api, the connections and timers run on the worker thread of the runtime.
+/// Unlike [Runtime::block_on] this does not panic if it is called from inside another runtime.
+#[derive(Debug)]
+pub(crate) struct SyncRuntime {
+    runtime: Option<Runtime>,
+}
+impl SyncRuntime {
+    pub(crate) fn new() -> io::Result<SyncRuntime> {
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("retarus-sync")
+            .enable_all()
+            .build()?;
+        Ok(SyncRuntime { runtime: Some(runtime) })
+    }
+
+    /// Blocks the current thread until the future completes. Inside of a multi thread runtime the worker hands its
+    /// other tasks to another thread first. A current thread runtime can not do this, its other tasks wait until the call is finished.
+    pub(crate) fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let runtime = self.runtime.as_ref().expect("the runtime is only taken on drop");
+        match Handle::try_current().map(|handle| handle.runtime_flavor()) {
+            Ok(RuntimeFlavor::MultiThread) => tokio::task::block_in_place(|| runtime.block_on(future)),
+            Ok(_) => {
+                // the future is polled on this thread, its connections and timers run on the worker of the runtime
+                let _guard = runtime.enter();
+                futures::executor::block_on(future)
+            }
+            Err(_) => runtime.block_on(future),
+        }
+    }
+}
+
+/// Dropping a runtime inside of an async context panics, so the runtime is shut down in the background.
+impl Drop for SyncRuntime {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// The runtime of [blocking](super::transport::blocking) with the `blocking` feature, created with the first call.
+#[cfg(feature = "blocking")]
+pub(crate) fn shared() -> &'static SyncRuntime {
+    static RUNTIME: std::sync::OnceLock<SyncRuntime> = std::sync::OnceLock::new();
+    RUNTIME.get_or_init(|| SyncRuntime::new().expect("the runtime for blocking calls could not be started"))
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_block_on_inside_multi_thread_runtime() {
+    // the call blocks the only worker of the outer runtime
+    let received = tokio::spawn(async {
+        let runtime = SyncRuntime::new().unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        // a task of the outer runtime, it only runs while the call blocks if the worker handed it over
+        tokio::spawn(async move { tx.send(()).unwrap() });
+        runtime.block_on(async {
+            tokio::task::spawn_blocking(move || rx.recv_timeout(std::time::Duration::from_secs(2))).await.unwrap()
+        })
+    });
+    assert!(received.await.unwrap().is_ok());
+    assert_eq!(SyncRuntime::new().unwrap().block_on(async { 1 }), 1);
+}
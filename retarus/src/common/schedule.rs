@@ -0,0 +1,218 @@
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, Datelike, Duration, NaiveDateTime, NaiveTime, SecondsFormat, TimeZone, Utc, Weekday};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScheduleError {
+    InPast,
+    EmptyInterval,
+    InvalidFormat(String),
+}
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScheduleError::InPast => write!(f, "The scheduled time must be in the future"),
+            ScheduleError::EmptyInterval => write!(f, "The end of an interval must be after its start"),
+            ScheduleError::InvalidFormat(value) => write!(f, "'{}' is not an iso-8601 interval (start/end)", value),
+        }
+    }
+}
+
+impl std::error::Error for ScheduleError {}
+
+/// Returns the given timestamp if it is in the future.
+pub fn validate_start(ts: DateTime<Utc>) -> Result<DateTime<Utc>, ScheduleError> {
+    if ts <= Utc::now() {
+        return Err(ScheduleError::InPast);
+    }
+    Ok(ts)
+}
+
+/// Serializes an optional timestamp with seconds precision like an [Interval], e.g. `2022-11-07T19:00:00Z`.
+pub(crate) mod optional_seconds {
+    use chrono::{DateTime, SecondsFormat, Utc};
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(ts: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match ts {
+            Some(ts) => serializer.serialize_str(&ts.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(value) => DateTime::parse_from_rfc3339(&value).map(|ts| Some(ts.with_timezone(&Utc))).map_err(de::Error::custom),
+            None => Ok(None),
+        }
+    }
+}
+
+/// A time period with a start and an end, serialized as iso-8601 interval e.g. `2022-11-07T19:00:00Z/2022-11-08T07:00:00Z`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+}
+impl Interval {
+    pub fn new(start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Interval, ScheduleError> {
+        if end <= start {
+            return Err(ScheduleError::EmptyInterval);
+        }
+        Ok(Interval { start, end })
+    }
+
+    pub fn start(&self) -> DateTime<Utc> {
+        self.start
+    }
+
+    pub fn end(&self) -> DateTime<Utc> {
+        self.end
+    }
+}
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}",
+            self.start.to_rfc3339_opts(SecondsFormat::Secs, true),
+            self.end.to_rfc3339_opts(SecondsFormat::Secs, true)
+        )
+    }
+}
+impl FromStr for Interval {
+    type Err = ScheduleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ScheduleError::InvalidFormat(s.to_string());
+        let (start, end) = s.split_once('/').ok_or_else(invalid)?;
+        let start = DateTime::parse_from_rfc3339(start).map_err(|_| invalid())?;
+        let end = DateTime::parse_from_rfc3339(end).map_err(|_| invalid())?;
+        Interval::new(start.with_timezone(&Utc), end.with_timezone(&Utc))
+    }
+}
+impl Serialize for Interval {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+impl<'de> Deserialize<'de> for Interval {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(de::Error::custom)
+    }
+}
+
+/// A window that recurs on the given days in a local time zone, e.g. weekdays from 20:00 to 08:00 in Europe/Berlin.
+/// If `to` is before `from`, the window ends on the next day.
+///
+/// ## Example
+/// ```rust
+/// use chrono::{NaiveTime, TimeZone, Utc};
+/// use retarus::common::schedule::RecurringWindow;
+///
+/// let nights = RecurringWindow::weekdays(
+///     NaiveTime::from_hms_opt(20, 0, 0).unwrap(),
+///     NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+///     chrono_tz::Europe::Berlin,
+/// );
+/// let from = Utc.with_ymd_and_hms(2022, 11, 7, 0, 0, 0).unwrap();
+/// let intervals = nights.expand(from, from + chrono::Duration::days(7));
+/// assert_eq!(intervals[0].to_string(), "2022-11-07T19:00:00Z/2022-11-08T07:00:00Z");
+/// ```
+#[derive(Debug, Clone)]
+pub struct RecurringWindow<Tz: TimeZone> {
+    days: Vec<Weekday>,
+    from: NaiveTime,
+    to: NaiveTime,
+    tz: Tz,
+}
+impl<Tz: TimeZone> RecurringWindow<Tz> {
+    pub fn new(days: Vec<Weekday>, from: NaiveTime, to: NaiveTime, tz: Tz) -> RecurringWindow<Tz> {
+        RecurringWindow { days, from, to, tz }
+    }
+
+    /// A window that starts on monday to friday.
+    pub fn weekdays(from: NaiveTime, to: NaiveTime, tz: Tz) -> RecurringWindow<Tz> {
+        let days = vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri];
+        RecurringWindow::new(days, from, to, tz)
+    }
+
+    /// A window that starts every day.
+    pub fn daily(from: NaiveTime, to: NaiveTime, tz: Tz) -> RecurringWindow<Tz> {
+        let days = vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri, Weekday::Sat, Weekday::Sun];
+        RecurringWindow::new(days, from, to, tz)
+    }
+
+    /// Get all occurrences of the window that overlap with the period between start and until.
+    pub fn expand(&self, start: DateTime<Utc>, until: DateTime<Utc>) -> Vec<Interval> {
+        let mut intervals = vec![];
+        // a window that started the day before can still overlap with start
+        let mut day = start.with_timezone(&self.tz).date_naive() - Duration::days(1);
+        let last_day = until.with_timezone(&self.tz).date_naive();
+
+        while day <= last_day {
+            if self.days.contains(&day.weekday()) {
+                let end_day = if self.to <= self.from { day + Duration::days(1) } else { day };
+                let window_start = self.to_utc(day.and_time(self.from));
+                let window_end = self.to_utc(end_day.and_time(self.to));
+                if window_end > start && window_start < until {
+                    if let Ok(interval) = Interval::new(window_start, window_end) {
+                        intervals.push(interval);
+                    }
+                }
+            }
+            day += Duration::days(1);
+        }
+        intervals
+    }
+
+    /// Local times that do not exist because of a daylight saving change are moved one hour forward.
+    fn to_utc(&self, local: NaiveDateTime) -> DateTime<Utc> {
+        self.tz
+            .from_local_datetime(&local)
+            .earliest()
+            .or_else(|| self.tz.from_local_datetime(&(local + Duration::hours(1))).earliest())
+            .map(|ts| ts.with_timezone(&Utc))
+            .unwrap_or_else(|| Utc.from_utc_datetime(&local))
+    }
+}
+
+#[test]
+fn test_interval_format() {
+    let interval: Interval = "2022-11-07T20:00:00+01:00/2022-11-08T08:00:00+01:00".parse().unwrap();
+    assert_eq!(interval.to_string(), "2022-11-07T19:00:00Z/2022-11-08T07:00:00Z");
+    assert_eq!(serde_json::to_string(&interval).unwrap(), "\"2022-11-07T19:00:00Z/2022-11-08T07:00:00Z\"");
+    assert!("2022-11-07T19:00:00Z".parse::<Interval>().is_err());
+    assert_eq!(
+        "2022-11-08T07:00:00Z/2022-11-07T19:00:00Z".parse::<Interval>(),
+        Err(ScheduleError::EmptyInterval)
+    );
+}
+
+#[test]
+fn test_expand_weekdays() {
+    let nights = RecurringWindow::weekdays(
+        NaiveTime::from_hms_opt(20, 0, 0).unwrap(),
+        NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        chrono_tz::Europe::Berlin,
+    );
+    // monday to monday, the first window starts monday evening
+    let from = Utc.with_ymd_and_hms(2022, 11, 7, 0, 0, 0).unwrap();
+    let intervals = nights.expand(from, from + Duration::days(7));
+    assert_eq!(intervals.len(), 5);
+    assert_eq!(intervals[4].to_string(), "2022-11-11T19:00:00Z/2022-11-12T07:00:00Z");
+
+    // the window of friday night overlaps with a saturday start, daylight saving time ends on 2022-10-30
+    let from = Utc.with_ymd_and_hms(2022, 10, 29, 5, 0, 0).unwrap();
+    let intervals = nights.expand(from, from + Duration::days(3));
+    assert_eq!(intervals[0].to_string(), "2022-10-28T18:00:00Z/2022-10-29T06:00:00Z");
+    assert_eq!(intervals[1].to_string(), "2022-10-31T19:00:00Z/2022-11-01T07:00:00Z");
+}
+
+#[test]
+fn test_validate_start() {
+    assert_eq!(validate_start(Utc::now() - Duration::minutes(1)), Err(ScheduleError::InPast));
+    assert!(validate_start(Utc::now() + Duration::minutes(1)).is_ok());
+}
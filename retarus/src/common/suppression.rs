@@ -0,0 +1,228 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use super::phone::PhoneNumber;
+
+/// Keywords that are treated as opt-out request when they are the first word of an inbound message.
+pub const DEFAULT_OPT_OUT_KEYWORDS: &[&str] = &["STOP", "STOPP", "UNSUBSCRIBE", "CANCEL", "END", "QUIT", "ABMELDEN"];
+
+/// A list of numbers that must not be contacted anymore. Clients with a suppression list remove these recipients before a job is sent.
+///
+/// Numbers are stored in E.164 format, so `+49...`, `0049...` and `49...` are treated as the same number.
+/// A list that was opened with [SuppressionList::open] writes every change to its file, one number per line.
+///
+/// ## Example
+/// ```rust
+/// use retarus::common::suppression::SuppressionList;
+///
+/// let list = SuppressionList::new();
+/// list.add("+4912310000000").unwrap();
+/// assert!(list.contains("004912310000000"));
+/// assert!(list.ingest_inbound("+4917600000000", "Stop please").unwrap());
+/// ```
+#[derive(Debug, Clone)]
+pub struct SuppressionList {
+    numbers: Arc<RwLock<HashSet<String>>>,
+    path: Option<PathBuf>,
+    keywords: Vec<String>,
+}
+impl SuppressionList {
+    /// Create a list that is only kept in memory.
+    pub fn new() -> SuppressionList {
+        SuppressionList {
+            numbers: Arc::default(),
+            path: None,
+            keywords: DEFAULT_OPT_OUT_KEYWORDS.iter().map(|k| k.to_string()).collect(),
+        }
+    }
+
+    /// Load the list from a file, the file is created with the first change if it does not exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<SuppressionList> {
+        let mut list = SuppressionList::new();
+        if path.as_ref().exists() {
+            list.import(File::open(path.as_ref())?)?;
+        }
+        list.path = Some(path.as_ref().to_path_buf());
+        Ok(list)
+    }
+
+    /// Replace the keywords that are recognized by [SuppressionList::ingest_inbound].
+    pub fn set_keywords(mut self, keywords: Vec<String>) -> SuppressionList {
+        self.keywords = keywords.into_iter().map(|k| k.to_uppercase()).collect();
+        self
+    }
+
+    pub fn add(&self, number: &str) -> io::Result<()> {
+        let mut numbers = self.numbers.write().unwrap();
+        if numbers.insert(normalize(number)) {
+            self.persist(&numbers)?;
+        }
+        Ok(())
+    }
+
+    pub fn remove(&self, number: &str) -> io::Result<()> {
+        let mut numbers = self.numbers.write().unwrap();
+        if numbers.remove(&normalize(number)) {
+            self.persist(&numbers)?;
+        }
+        Ok(())
+    }
+
+    pub fn contains(&self, number: &str) -> bool {
+        self.numbers.read().unwrap().contains(&normalize(number))
+    }
+
+    pub fn len(&self) -> usize {
+        self.numbers.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Add all numbers of the reader, one number per line. Empty lines and lines starting with `#` are skipped.
+    pub fn import<R: Read>(&self, reader: R) -> io::Result<usize> {
+        let mut count = 0;
+        let mut numbers = self.numbers.write().unwrap();
+        for line in BufReader::new(reader).lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if numbers.insert(normalize(line)) {
+                count += 1;
+            }
+        }
+        if count > 0 {
+            self.persist(&numbers)?;
+        }
+        Ok(count)
+    }
+
+    /// Write all numbers sorted to the writer, one number per line.
+    pub fn export<W: Write>(&self, writer: W) -> io::Result<()> {
+        write_sorted(&self.numbers.read().unwrap(), writer)
+    }
+
+    /// Checks if an inbound message is an opt-out request and adds the sender to the list.
+    /// Returns true if the message started with one of the keywords, and an error if the sender could not be written to the file of the list.
+    /// The sender is suppressed in memory even then.
+    pub fn ingest_inbound(&self, from: &str, text: &str) -> io::Result<bool> {
+        let first_word = text
+            .split_whitespace()
+            .next()
+            .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()).to_uppercase())
+            .unwrap_or_default();
+        if !self.keywords.contains(&first_word) {
+            return Ok(false);
+        }
+        self.add(from)?;
+        Ok(true)
+    }
+
+    /// Split the given numbers into the ones that may be contacted and the suppressed ones.
+    pub fn partition(&self, numbers: Vec<String>) -> (Vec<String>, Vec<String>) {
+        let suppressed = self.numbers.read().unwrap();
+        numbers.into_iter().partition(|n| !suppressed.contains(&normalize(n)))
+    }
+
+    /// Called while the write lock is held, so the changes of all clones of the list are written one after another.
+    fn persist(&self, numbers: &HashSet<String>) -> io::Result<()> {
+        static WRITES: AtomicUsize = AtomicUsize::new(0);
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        // write to a temporary file next to the list first, so a crash never leaves a truncated list behind
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(format!(".{}-{}.tmp", std::process::id(), WRITES.fetch_add(1, Ordering::Relaxed)));
+        let result = File::create(&tmp).and_then(|file| write_sorted(numbers, file)).and_then(|_| fs::rename(&tmp, path));
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        result
+    }
+}
+
+impl Default for SuppressionList {
+    fn default() -> Self {
+        SuppressionList::new()
+    }
+}
+
+fn write_sorted<W: Write>(numbers: &HashSet<String>, mut writer: W) -> io::Result<()> {
+    let mut numbers: Vec<&String> = numbers.iter().collect();
+    numbers.sort();
+    for number in numbers {
+        writeln!(writer, "{}", number)?;
+    }
+    Ok(())
+}
+
+/// Numbers that can not be parsed are stored as given, so they can still be suppressed.
+fn normalize(number: &str) -> String {
+    match PhoneNumber::from_str(number) {
+        Ok(number) => number.e164(),
+        Err(_) => number.trim().to_string(),
+    }
+}
+
+#[test]
+fn test_suppression_list() {
+    let list = SuppressionList::new();
+    list.add("004912310000000").unwrap();
+    list.add("+4912310000000").unwrap();
+    assert_eq!(list.len(), 1);
+    assert!(list.contains("4912310000000"));
+
+    let (allowed, suppressed) = list.partition(vec!["+49 123 10000000".to_string(), "+4917600000000".to_string()]);
+    assert_eq!(allowed, vec!["+4917600000000".to_string()]);
+    assert_eq!(suppressed.len(), 1);
+
+    list.remove("+4912310000000").unwrap();
+    assert!(list.is_empty());
+
+    assert!(!list.ingest_inbound("+4917600000000", "Don't stop").unwrap());
+    assert!(list.ingest_inbound("+4917600000000", "stop.").unwrap());
+    assert!(list.contains("+4917600000000"));
+}
+
+#[test]
+fn test_suppression_list_file() {
+    let path = std::env::temp_dir().join(format!("retarus_suppression_{}.txt", std::process::id()));
+    let list = SuppressionList::open(&path).unwrap();
+    assert_eq!(list.import("# opt-outs\n+4912310000000\n\n+4917600000000\n".as_bytes()).unwrap(), 2);
+    list.remove("+4917600000000").unwrap();
+
+    let reopened = SuppressionList::open(&path).unwrap();
+    let mut exported = vec![];
+    reopened.export(&mut exported).unwrap();
+    assert_eq!(String::from_utf8(exported).unwrap(), "+4912310000000\n");
+    fs::remove_file(path).unwrap();
+
+    // the temporary file is written next to the list, also for a list that ends with .tmp
+    let dir = std::env::temp_dir().join(format!("retarus_suppression_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let list = SuppressionList::open(dir.join("list.tmp")).unwrap();
+    let clone = list.clone();
+    let writers: Vec<_> = (0..4)
+        .map(|i| {
+            let list = clone.clone();
+            std::thread::spawn(move || list.add(&format!("+491231000000{}", i)).unwrap())
+        })
+        .collect();
+    writers.into_iter().for_each(|w| w.join().unwrap());
+    assert_eq!(SuppressionList::open(dir.join("list.tmp")).unwrap().len(), 4);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -1,49 +1,293 @@
-use std::{error::Error, time::Duration};
-use futures::Future;
-use hyper::{client::HttpConnector, Body, Client, Method, Request, Response};
+use std::{error::Error, sync::Arc, time::{Duration, Instant}};
+use futures::{stream::FuturesUnordered, Future, StreamExt};
+use hyper::{client::HttpConnector, header::HeaderValue, Body, Client, Method, Request, Response, StatusCode};
 use hyper_tls::HttpsConnector;
 use reqwest::{multipart::Form};
 use serde::{Serialize};
 use tokio_compat_02::FutureExt;
+use tracing::{field::Empty, Instrument, Span};
 
-use crate::webexpress::job::WebexpressJob;
+use super::{
+    creds::Credentials,
+    health::{Health, ServerHealth},
+    limit::AccountLimiters,
+    metrics::{FailureKind, Metrics, NoMetrics},
+    provider::{CredentialError, CredentialProvider, StaticCredentials},
+    uri::{Region, RegionUri},
+};
 
-use super::creds::Credentials;
+const USER_AGENT: &str = concat!("retarus-rust/", env!("CARGO_PKG_VERSION"));
+
+/// Timeouts, retries and connection pooling used for all requests of a client.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransportConfig {
+    /// Maximum time for a single attempt, including reading the response headers.
+    pub timeout: Duration,
+    /// How often a request is repeated if no connection to the server could be established.
+    /// Requests that reached the server are never repeated, so a job is not sent twice.
+    pub connect_retries: u32,
+    pub pool_idle_timeout: Duration,
+    /// Applies to the pool of the json requests and to the pool of the multipart uploads each, see [Transporter::form_post_with].
+    pub pool_max_idle_per_host: usize,
+    /// After this many failed calls in a row a server is skipped, a call fails if the server can not be reached or answers with 5xx.
+    pub circuit_failures: u32,
+    /// How long a server is skipped before a call probes it again.
+    pub circuit_open: Duration,
+}
+impl Default for TransportConfig {
+    fn default() -> Self {
+        TransportConfig {
+            timeout: Duration::from_secs(60),
+            connect_retries: 2,
+            pool_idle_timeout: Duration::from_secs(60),
+            pool_max_idle_per_host: 10,
+            circuit_failures: 3,
+            circuit_open: Duration::from_secs(30),
+        }
+    }
+}
 
 /// Specifies the region and your login credentials.
 #[derive(Debug, Clone)]
 pub struct Transporter {
-    /// Which server should be used to send the fax to.
-    credentials: Credentials,
+    /// Asked for the credentials of every request.
+    credentials: Arc<dyn CredentialProvider>,
+    config: TransportConfig,
+    pool: HttpPool,
+    /// The label of the service for the metrics, e.g. "fax".
+    service: &'static str,
+    metrics: Arc<dyn Metrics>,
+    /// Shared by the clones of the client, see [RateLimit](super::limit::RateLimit).
+    limiter: Option<AccountLimiters>,
+    /// The account whose limiter the requests wait for, e.g. the customer number of a fax call.
+    /// Without it the username of the credentials of each request is used.
+    account: Option<String>,
+    /// The health of the servers of the region, shared by the clones of the client.
+    health: Arc<Health>,
+}
+
+/// The http clients and their connection pools, cloning shares the pools. Used to let the clients of [Retarus](crate::Retarus) share their connections.
+///
+/// The json requests and the multipart uploads use separate pools. hyper has no multipart support and the form of reqwest
+/// can only be sent by a reqwest client, which always keeps its own pool. Moving the json requests to reqwest would change
+/// the hyper based fax and sms clients, so both pools are built from the same [TransportConfig] instead.
+/// Only webexpress uploads multipart forms, so in practice a connection to a host is in one of the pools.
+#[derive(Debug, Clone)]
+pub(crate) struct HttpPool {
     client: Client<HttpsConnector<HttpConnector>>,
+    /// Used for multipart uploads, with the same timeouts, pool settings and user agent as the hyper client.
+    form_client: reqwest::Client,
+}
+impl HttpPool {
+    pub(crate) fn new(config: &TransportConfig) -> HttpPool {
+        let https = HttpsConnector::new();
+        let client = Client::builder()
+            .pool_idle_timeout(config.pool_idle_timeout)
+            .pool_max_idle_per_host(config.pool_max_idle_per_host)
+            .build::<_, hyper::Body>(https);
+        let form_client = reqwest::Client::builder()
+            .pool_idle_timeout(config.pool_idle_timeout)
+            .pool_max_idle_per_host(config.pool_max_idle_per_host)
+            .timeout(config.timeout)
+            .user_agent(USER_AGENT)
+            .build()
+            .expect("the http client configuration is valid");
+        HttpPool { client, form_client }
+    }
 }
 
 impl Transporter {
     /// Creates a new Transporter that contains the endpoint_uri, credentials and a http client that pools the connections and is able to communicate with https endpoints.
     pub fn new(credentials: Credentials) -> Transporter {
-        let https = HttpsConnector::new();
-        let client = Client::builder()
-            .pool_idle_timeout(Duration::from_secs(60))
-            .pool_max_idle_per_host(10)
-            .build::<_, hyper::Body>(https);
-        Transporter {
-            credentials,
-            client,
+        Transporter::with_config(credentials, TransportConfig::default())
+    }
+
+    pub fn with_config(credentials: Credentials, config: TransportConfig) -> Transporter {
+        Transporter::with_provider(Arc::new(StaticCredentials::from(credentials)), config)
+    }
+
+    /// Asks the provider for the credentials of every request, see [CredentialProvider].
+    pub fn with_provider(credentials: Arc<dyn CredentialProvider>, config: TransportConfig) -> Transporter {
+        let pool = HttpPool::new(&config);
+        Transporter::with_pool(credentials, config, pool)
+    }
+
+    pub(crate) fn with_pool(credentials: Arc<dyn CredentialProvider>, config: TransportConfig, pool: HttpPool) -> Transporter {
+        let health = Arc::new(Health::new(&RegionUri::new(Region::Custom(String::new()), "", vec![]), config.circuit_failures, config.circuit_open));
+        Transporter { credentials, config, pool, service: "", metrics: Arc::new(NoMetrics), limiter: None, account: None, health }
+    }
+
+    /// Track the health of the servers of the region, see [Transporter::find].
+    pub(crate) fn with_region(mut self, region_uri: &RegionUri) -> Transporter {
+        self.health = Arc::new(Health::new(region_uri, self.config.circuit_failures, self.config.circuit_open));
+        self
+    }
+
+    /// The circuit state and latency of each server of the region.
+    pub(crate) fn server_health(&self) -> Vec<ServerHealth> {
+        self.health.snapshot()
+    }
+
+    /// Report the measurements of the requests to the metrics, labeled with the service.
+    pub(crate) fn with_metrics(mut self, service: &'static str, metrics: Arc<dyn Metrics>) -> Transporter {
+        self.service = service;
+        self.metrics = metrics;
+        self
+    }
+
+    /// Every attempt waits for the limiter of its account before it is sent.
+    pub(crate) fn with_limiter(mut self, limiter: Option<AccountLimiters>) -> Transporter {
+        self.limiter = limiter;
+        self
+    }
+
+    /// A clone whose requests wait for the limiter of the account, it shares the pools, limiters, health and metrics.
+    pub(crate) fn for_account(&self, account: &str) -> Transporter {
+        Transporter { account: Some(account.to_string()), ..self.clone() }
+    }
+
+    /// Waits until the limit of the account allows another request. The slot is released when the permit is dropped,
+    /// which happens once the status and headers arrived, reading the body does not count as in flight.
+    async fn acquire(&self, credentials: &Credentials) -> Option<tokio::sync::OwnedSemaphorePermit> {
+        let limiter = self.limiter.as_ref()?;
+        let account = self.account.as_deref().unwrap_or(credentials.username());
+        Some(limiter.get(account).acquire().await)
+    }
+
+    /// The metrics of the client, for the measurements the client records itself.
+    pub(crate) fn metrics(&self) -> &dyn Metrics {
+        self.metrics.as_ref()
+    }
+
+    pub(crate) fn job_submitted(&self) {
+        self.metrics.job_submitted(self.service);
+    }
+
+    /// The server refused the job, e.g. because of an invalid number or a missing permission.
+    pub(crate) fn job_rejected(&self) {
+        self.metrics.call_failed(self.service, FailureKind::Rejected);
+    }
+
+    /// The server does not know the job or could not be reached, the client asks the next server of the region.
+    pub(crate) fn failover(&self, server: &str) {
+        tracing::debug!(server, "the server did not return the job, the next server is asked");
+        self.metrics.failover(self.service, server);
+    }
+
+    /// The credentials of a request, a failure of the provider is counted.
+    /// A provider that blocks, e.g. to read a file, is asked on a blocking thread so it does not stall the runtime.
+    async fn request_credentials(&self) -> Result<Arc<Credentials>, CredentialError> {
+        let result = if self.credentials.blocks() {
+            let provider = self.credentials.clone();
+            tokio::task::spawn_blocking(move || provider.credentials()).await.expect("the credential provider panicked")
+        } else {
+            self.credentials.credentials()
+        };
+        result.inspect_err(|_| self.metrics.call_failed(self.service, FailureKind::Credentials))
+    }
+
+    fn authorization(credentials: &Credentials) -> HeaderValue {
+        let mut value = HeaderValue::from_str(credentials.authorization().expose()).expect("base64 is a valid header value");
+        value.set_sensitive(true);
+        value
+    }
+
+    /// Send the request to the servers of the region until one answers with 200 OK. Servers with an open circuit are skipped,
+    /// the others are asked in the order of their latency. Returns None only if every server of the region was asked and
+    /// answered with 404 Not Found. If a server failed, answered with another status, e.g. 401 or 503, or was skipped,
+    /// the job may be on it, so an error is returned.
+    pub(crate) async fn find<F, Fut>(&self, mut request: F) -> Result<Option<Response<Body>>, Box<dyn Error>>
+    where
+        F: FnMut(String) -> Fut,
+        Fut: Future<Output = Result<Response<Body>, Box<dyn Error>>>,
+    {
+        let servers = self.health.servers();
+        let skipped = self.health.skipped(&servers);
+        let mut error = None;
+        let mut failed_status = None;
+        let mut servers = servers.into_iter().peekable();
+        while let Some(server) = servers.next() {
+            match request(server.clone()).await {
+                Ok(response) if response.status() == StatusCode::OK => return Ok(Some(response)),
+                Ok(response) if response.status() == StatusCode::NOT_FOUND => {}
+                Ok(response) => {
+                    error = Some(format!("The server {} answered with {}", server, response.status()).into());
+                    failed_status = Some(response.status());
+                }
+                Err(err) => error = Some(err),
+            }
+            if servers.peek().is_some() {
+                self.failover(&server);
+            }
+        }
+        if let Some(status) = failed_status {
+            self.status_failed(status);
+        }
+        match error {
+            Some(err) => Err(err),
+            None if !skipped.is_empty() => Err(skipped_error(&skipped)),
+            None => Ok(None),
+        }
+    }
+
+    /// A server of a lookup answered with another status than 200 or 404, counted once per call.
+    fn status_failed(&self, status: StatusCode) {
+        let kind = if status.is_server_error() { FailureKind::Http } else { FailureKind::Rejected };
+        self.metrics.call_failed(self.service, kind);
+    }
+
+    /// Send the request to all servers of the region at once, used if a report can be on any of them.
+    /// Lookups are sent to the servers of [Transporter::find], the others are cancelled after the first 200 OK and they return like it.
+    /// With complete_all, e.g. for a delete, every server of the region is asked regardless of its circuit and every
+    /// request is finished, so the report is not left on a server. Then an error is returned if a server neither
+    /// confirmed the delete nor answered 404 Not Found.
+    pub(crate) async fn find_parallel<F, Fut>(&self, mut request: F, complete_all: bool) -> Result<Option<Response<Body>>, Box<dyn Error>>
+    where
+        F: FnMut(String) -> Fut,
+        Fut: Future<Output = Result<Response<Body>, Box<dyn Error>>>,
+    {
+        let servers = if complete_all { self.health.region_servers() } else { self.health.servers() };
+        let skipped = self.health.skipped(&servers);
+        let mut pending: FuturesUnordered<_> = servers.into_iter().map(&mut request).collect();
+        let mut found = None;
+        let mut error = None;
+        let mut failed_status = None;
+        while let Some(result) = pending.next().await {
+            match result {
+                Ok(response) if response.status() == StatusCode::OK && found.is_none() => {
+                    found = Some(response);
+                    if !complete_all {
+                        break;
+                    }
+                }
+                Ok(response) if matches!(response.status(), StatusCode::OK | StatusCode::NOT_FOUND) => {}
+                Ok(response) if complete_all => {
+                    error = Some(format!("A server answered the delete with {}, the report may still be on it", response.status()).into());
+                    failed_status = Some(response.status());
+                }
+                Ok(response) => {
+                    error = Some(format!("A server answered the lookup with {}, the report may be on it", response.status()).into());
+                    failed_status = Some(response.status());
+                }
+                Err(err) => error = Some(err),
+            }
+        }
+        match (found, error) {
+            (Some(response), Some(_)) if !complete_all => Ok(Some(response)),
+            (_, Some(err)) => {
+                if let Some(status) = failed_status {
+                    self.status_failed(status);
+                }
+                Err(err)
+            }
+            (Some(response), None) => Ok(Some(response)),
+            (None, None) if !skipped.is_empty() => Err(skipped_error(&skipped)),
+            (None, None) => Ok(None),
         }
     }
 
     pub async fn get(&self, uri: String) -> Result<Response<Body>, Box<dyn Error>> {
-        let req = Request::builder()
-            .uri(uri)
-            .method(Method::GET)
-            .header(
-                "Authorization",
-                format!("Basic {}", self.credentials.clone().encode()),
-            )
-            .header("Content-Type", "application/json")
-            .body(Body::default())?;
-        let response = self.client.request(req).compat().await?;
-        Ok(response)
+        self.send(Method::GET, uri, vec![]).await
     }
 
     pub async fn post<T: Serialize>(
@@ -52,50 +296,166 @@ impl Transporter {
         payload: T,
     ) -> Result<Response<Body>, Box<dyn Error>> {
         let payload = serde_json::to_string(&payload)?.as_bytes().to_owned();
-
-        let req = Request::builder()
-            .uri(uri)
-            .method(Method::POST)
-            .header("Content-Type", "application/json")
-            .header(
-                "Authorization",
-                format!("Basic {}", self.credentials.clone().encode()),
-            )
-            .body(Body::from(payload))?;
-        let response = self.client.request(req).compat().await?;
-        Ok(response)
+        self.send(Method::POST, uri, payload).await
     }
 
-
+    /// Send the form of a webexpress job, it is only sent once and not repeated if the connection fails.
+    #[cfg(feature = "webexpress")]
+    #[deprecated(note = "use form_post_with, it creates the form again for every retry")]
     pub async fn form_post(
         &self,
         uri: String,
-        payload: WebexpressJob,
+        payload: crate::webexpress::job::WebexpressJob,
     ) -> Result<reqwest::Response, Box<dyn Error>> {
-        let client = reqwest::Client::new();
-        let job: Form = payload.into();
-        println!("Webexpress job {:?}", job);
-        let response = client
-            .post(uri)
-            .multipart(job)
-            .send().await?;
-        Ok(response)
+        let mut payload = Some(payload);
+        self.form_post_with(uri, move |_| Ok(payload.take().ok_or("The form can only be sent once")?.try_into()?)).await
+    }
+
+    /// Send a multipart form, make_form is called again for every retry because a form can only be sent once.
+    /// It gets the credentials of the attempt, e.g. for a portal that also expects them as form fields.
+    /// The form is sent over the pool for uploads, with the same credentials, limits, retries and metrics as the json requests.
+    /// If it fails for a retry, e.g. because the form contains a stream, the error of the previous attempt is returned.
+    pub async fn form_post_with<F>(
+        &self,
+        uri: String,
+        make_form: F,
+    ) -> Result<reqwest::Response, Box<dyn Error>>
+    where
+        F: FnMut(&Credentials) -> Result<Form, Box<dyn Error>>,
+    {
+        let server = server(&uri);
+        let span = tracing::debug_span!("http_request", method = "POST", server = %server, attempt = Empty, status = Empty, latency_ms = Empty);
+        let started = Instant::now();
+        let result = self.form_attempts(uri, make_form).instrument(span).await;
+        self.health.record(&server, result.as_ref().is_ok_and(|r| !r.status().is_server_error()), started.elapsed());
+        result
     }
 
+    async fn form_attempts<F>(&self, uri: String, mut make_form: F) -> Result<reqwest::Response, Box<dyn Error>>
+    where
+        F: FnMut(&Credentials) -> Result<Form, Box<dyn Error>>,
+    {
+        let server = server(&uri);
+        let mut attempt = 0;
+        let mut last_error: Option<reqwest::Error> = None;
+        loop {
+            Span::current().record("attempt", attempt + 1);
+            let credentials = self.request_credentials().await?;
+            let form = match make_form(&credentials) {
+                Ok(form) => form,
+                Err(err) => return Err(last_error.map_or(err, Into::into)),
+            };
+            let _permit = self.acquire(&credentials).await;
+            let started = Instant::now();
+            let result = self
+                .pool
+                .form_client
+                .post(&uri)
+                .header("Authorization", Transporter::authorization(&credentials))
+                .multipart(form)
+                .send()
+                .await;
+            let latency = started.elapsed();
+            Span::current().record("latency_ms", latency.as_millis() as u64);
+            self.metrics.http_request(self.service, &server, result.as_ref().ok().map(|r| r.status().as_u16()), latency);
+            match result {
+                Err(err) if err.is_connect() && attempt < self.config.connect_retries => {
+                    tracing::warn!(attempt = attempt + 1, error = %err, "could not connect, the request is repeated");
+                    attempt += 1;
+                    last_error = Some(err);
+                }
+                Ok(response) => {
+                    Span::current().record("status", response.status().as_u16());
+                    return Ok(response);
+                }
+                Err(err) => {
+                    tracing::warn!(error = %err, "the request failed");
+                    let kind = if err.is_connect() {
+                        FailureKind::Connect
+                    } else if err.is_timeout() {
+                        FailureKind::Timeout
+                    } else {
+                        FailureKind::Http
+                    };
+                    self.metrics.call_failed(self.service, kind);
+                    return Err(err.into());
+                }
+            }
+        }
+    }
 
     pub async fn delete(&self, uri: String) -> Result<Response<Body>, Box<dyn Error>> {
-        let req = Request::builder()
-            .uri(uri)
-            .method(Method::DELETE)
-            .header(
-                "Authorization",
-                format!("Basic {}", self.credentials.clone().encode()),
-            )
-            .header("Content-Type", "application/json")
-            .body(Body::default())?;
-        let response = self.client.request(req).compat().await?;
-        Ok(response)
+        self.send(Method::DELETE, uri, vec![]).await
+    }
+
+    async fn send(&self, method: Method, uri: String, payload: Vec<u8>) -> Result<Response<Body>, Box<dyn Error>> {
+        let server = server(&uri);
+        let span = tracing::debug_span!("http_request", method = %method, server = %server, attempt = Empty, status = Empty, latency_ms = Empty);
+        let started = Instant::now();
+        let result = self.send_attempts(method, uri, payload).instrument(span).await;
+        self.health.record(&server, result.as_ref().is_ok_and(|r| !r.status().is_server_error()), started.elapsed());
+        result
     }
+
+    async fn send_attempts(&self, method: Method, uri: String, payload: Vec<u8>) -> Result<Response<Body>, Box<dyn Error>> {
+        let server = server(&uri);
+        let mut attempt = 0;
+        loop {
+            Span::current().record("attempt", attempt + 1);
+            let credentials = self.request_credentials().await?;
+            let _permit = self.acquire(&credentials).await;
+            let started = Instant::now();
+            let req = Request::builder()
+                .uri(&uri)
+                .method(method.clone())
+                .header("Content-Type", "application/json")
+                .header("User-Agent", USER_AGENT)
+                .header("Authorization", Transporter::authorization(&credentials))
+                .body(Body::from(payload.clone()))?;
+            let response = tokio::time::timeout(self.config.timeout, self.pool.client.request(req).compat()).await;
+            let latency = started.elapsed();
+            Span::current().record("latency_ms", latency.as_millis() as u64);
+            let status = match &response {
+                Ok(Ok(response)) => Some(response.status().as_u16()),
+                _ => None,
+            };
+            self.metrics.http_request(self.service, &server, status, latency);
+            match response {
+                Err(_) => {
+                    tracing::warn!(timeout = ?self.config.timeout, "the request timed out");
+                    self.metrics.call_failed(self.service, FailureKind::Timeout);
+                    return Err(format!("The request to {} timed out after {:?}", uri, self.config.timeout).into());
+                }
+                Ok(Err(err)) if err.is_connect() && attempt < self.config.connect_retries => {
+                    tracing::warn!(attempt = attempt + 1, error = %err, "could not connect, the request is repeated");
+                    attempt += 1;
+                }
+                Ok(Ok(response)) => {
+                    Span::current().record("status", response.status().as_u16());
+                    return Ok(response);
+                }
+                Ok(Err(err)) => {
+                    tracing::warn!(error = %err, "the request failed");
+                    let kind = if err.is_connect() { FailureKind::Connect } else { FailureKind::Http };
+                    self.metrics.call_failed(self.service, kind);
+                    return Err(err.into());
+                }
+            }
+        }
+    }
+}
+
+/// The scheme and host of an uri for the spans, the path contains customer numbers and job ids that the clients record themselves.
+pub(crate) fn server(uri: &str) -> String {
+    match uri.parse::<hyper::Uri>() {
+        Ok(uri) => format!("{}://{}", uri.scheme_str().unwrap_or("https"), uri.authority().map(|a| a.as_str()).unwrap_or_default()),
+        Err(_) => String::new(),
+    }
+}
+
+/// The job may be on a server that was not asked, so it is not reported as missing.
+fn skipped_error(skipped: &[String]) -> Box<dyn Error> {
+    format!("The job was not found, the servers {} were not asked because their circuit is open", skipped.join(", ")).into()
 }
 
 pub async fn response_to_body(resp: Response<Body>) -> Result<String, Box<dyn Error>> {
@@ -108,11 +468,270 @@ pub async fn response_to_body(resp: Response<Body>) -> Result<String, Box<dyn Er
 
 
 /// Takes a future in and blocks the current thread until the future completes,
-/// used if your program should run synchronously.
-pub fn blocking<F: Future>(mut future: F) -> F::Output 
-{
-    let runtime = tokio::runtime::Runtime::new().unwrap();
-    
-    let res = runtime.block_on(future);
-    res
+/// used if your program should run synchronously. It is safe to call from inside another runtime.
+/// With the `blocking` feature all calls share one runtime and reuse their connections, without it each call starts
+/// a runtime of its own. For a complete sync api enable the feature and use the clients of the `blocking` module.
+#[cfg(feature = "blocking")]
+pub fn blocking<F: Future>(future: F) -> F::Output {
+    super::runtime::shared().block_on(future)
+}
+
+/// Takes a future in and blocks the current thread until the future completes,
+/// used if your program should run synchronously. It is safe to call from inside another runtime.
+/// Each call starts a runtime of its own, enable the `blocking` feature to share one runtime and the clients of the `blocking` module.
+#[cfg(not(feature = "blocking"))]
+pub fn blocking<F: Future>(future: F) -> F::Output {
+    super::runtime::SyncRuntime::new().expect("the runtime for the blocking call could not be started").block_on(future)
+}
+
+#[tokio::test]
+async fn test_form_post_headers_and_retries() {
+    use hyper::service::{make_service_fn, service_fn};
+
+    let make_service = make_service_fn(|_| async {
+        Ok::<_, hyper::Error>(service_fn(|req: Request<Body>| async move {
+            let header = |name| req.headers().get(name).map(|v| v.to_str().unwrap().to_string()).unwrap_or_default();
+            let body = format!("{}\n{}", header("authorization"), header("user-agent"));
+            Ok::<_, hyper::Error>(Response::new(Body::from(body)))
+        }))
+    });
+    let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+    let addr = server.local_addr();
+    tokio::spawn(server);
+
+    let credentials = Credentials::new("user", "password");
+    let transporter = Transporter::new(credentials.clone());
+    let res = transporter.form_post_with(format!("http://{}/upload", addr), |_| Ok(Form::new().text("a", "b"))).await.unwrap();
+    assert_eq!(res.text().await.unwrap(), format!("{}\n{}", credentials.authorization().expose(), USER_AGENT));
+    let res = transporter.get(format!("http://{}/reports", addr)).await.unwrap();
+    assert_eq!(response_to_body(res).await.unwrap(), format!("{}\n{}", credentials.authorization().expose(), USER_AGENT));
+
+    // nothing listens on the port of a dropped listener, every attempt builds a new form
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let mut forms = 0;
+    let res = transporter.form_post_with(format!("http://{}/upload", closed), |_| {
+        forms += 1;
+        Ok(Form::new())
+    });
+    assert!(res.await.is_err());
+    assert_eq!(forms, TransportConfig::default().connect_retries + 1);
+}
+
+#[cfg(feature = "webexpress")]
+#[tokio::test]
+#[allow(deprecated)]
+async fn test_deprecated_form_post() {
+    use hyper::service::{make_service_fn, service_fn};
+    use crate::webexpress::job::WebexpressJob;
+
+    let make_service = make_service_fn(|_| async {
+        Ok::<_, hyper::Error>(service_fn(|req: Request<Body>| async move {
+            let body = hyper::body::to_bytes(req.into_body()).await?;
+            Ok::<_, hyper::Error>(Response::new(Body::from(body)))
+        }))
+    });
+    let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+    let addr = server.local_addr();
+    tokio::spawn(server);
+
+    let mut job = WebexpressJob::builder()
+        .set_list_name("list".to_string())
+        .set_content("list.csv".to_string(), b"id,SMS\r\n1,+4917600000000\r\n".to_vec())
+        .set_default_country_code("+49".parse().unwrap())
+        .build()
+        .unwrap();
+    job.j_username = Some("user".to_string());
+    job.j_password = Some("password".to_string());
+    let transporter = Transporter::new(Credentials::new("user", "password"));
+    let res = transporter.form_post(format!("http://{}/upload", addr), job).await.unwrap();
+    let body = res.text().await.unwrap();
+    assert!(body.contains("name=\"j_username\"\r\n\r\nuser\r\n"));
+    assert!(body.contains("1,+4917600000000"));
+}
+
+#[tokio::test]
+async fn test_limiter_per_account() {
+    use super::limit::{select_limiter, RateLimit};
+
+    let limiters = select_limiter("fax", Some(RateLimit::new(1000.0, 10, 1))).unwrap();
+    let transporter = Transporter::new(Credentials::new("user", "password")).with_limiter(limiters);
+    let credentials = Credentials::new("user", "password");
+    let permit = transporter.acquire(&credentials).await;
+    assert!(permit.is_some());
+    // another account has a limiter of its own, clones and the account of the username share one
+    let wait = Duration::from_millis(20);
+    assert!(tokio::time::timeout(wait, transporter.for_account("12345").acquire(&credentials)).await.is_ok());
+    assert!(tokio::time::timeout(wait, transporter.clone().acquire(&credentials)).await.is_err());
+    assert!(tokio::time::timeout(wait, transporter.for_account("user").acquire(&credentials)).await.is_err());
+    drop(permit);
+    assert!(tokio::time::timeout(wait, transporter.acquire(&credentials)).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_metrics_of_failed_request() {
+    use std::sync::Mutex;
+
+    #[derive(Debug, Default)]
+    struct Recorder(Mutex<Vec<String>>);
+    impl Metrics for Recorder {
+        fn call_failed(&self, service: &'static str, kind: FailureKind) {
+            self.0.lock().unwrap().push(format!("{} {}", service, kind.as_str()));
+        }
+        fn http_request(&self, service: &'static str, server: &str, status: Option<u16>, _latency: Duration) {
+            self.0.lock().unwrap().push(format!("{} {} {:?}", service, server, status));
+        }
+    }
+
+    let recorder = Arc::new(Recorder::default());
+    let transporter = Transporter::new(Credentials::new("user", "password")).with_metrics("fax", recorder.clone());
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    assert!(transporter.get(format!("http://{}/rest/v1/12345/fax", closed)).await.is_err());
+    let attempt = format!("fax http://{} None", closed);
+    assert_eq!(*recorder.0.lock().unwrap(), vec![attempt.clone(), attempt.clone(), attempt, "fax connect".to_string()]);
+}
+
+#[tokio::test]
+async fn test_find_skips_unreachable_server() {
+    use super::health::CircuitState;
+    use hyper::service::{make_service_fn, service_fn};
+    use std::sync::Mutex;
+
+    #[derive(Debug, Default)]
+    struct Failovers(Mutex<Vec<String>>, Mutex<Vec<&'static str>>);
+    impl Metrics for Failovers {
+        fn failover(&self, _service: &'static str, server: &str) {
+            self.0.lock().unwrap().push(server.to_string());
+        }
+        fn call_failed(&self, _service: &'static str, kind: FailureKind) {
+            self.1.lock().unwrap().push(kind.as_str());
+        }
+    }
+
+    let make_service = make_service_fn(|_| async {
+        Ok::<_, hyper::Error>(service_fn(|req: Request<Body>| async move {
+            let path = req.uri().path();
+            let status = match path.strip_prefix("/status/") {
+                Some(code) => StatusCode::from_bytes(code.as_bytes()).unwrap(),
+                None if path.ends_with("/known") => StatusCode::OK,
+                None => StatusCode::NOT_FOUND,
+            };
+            Ok::<_, hyper::Error>(Response::builder().status(status).body(Body::empty()).unwrap())
+        }))
+    });
+    let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+    let up = format!("http://{}", server.local_addr());
+    tokio::spawn(server);
+    let down = format!("http://{}", std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap());
+
+    let config = TransportConfig { connect_retries: 0, circuit_failures: 1, ..TransportConfig::default() };
+    let region = RegionUri::new(Region::Custom(String::new()), "", vec![down.as_str(), up.as_str()]);
+    let failovers = Arc::new(Failovers::default());
+    let transporter = Transporter::with_config(Credentials::new("user", "password"), config).with_region(&region).with_metrics("fax", failovers.clone());
+    let mut asked = vec![];
+    let found = transporter.find(|server| {
+        asked.push(server.clone());
+        transporter.get(format!("{}/known", server))
+    });
+    assert!(found.await.unwrap().is_some());
+    assert_eq!(asked, vec![down.clone(), up.clone()]);
+
+    // the circuit of the unreachable server is open, only the other server is asked
+    let health = transporter.server_health();
+    assert_eq!((health[0].state, health[1].state), (CircuitState::Open, CircuitState::Closed));
+    assert!(health[1].latency.is_some());
+    asked.clear();
+    let found = transporter.find(|server| {
+        asked.push(server.clone());
+        transporter.get(format!("{}/unknown", server))
+    });
+    assert!(found.await.unwrap_err().to_string().contains(&down));
+    assert_eq!(asked, vec![up.clone()]);
+    // only the move from the unreachable server to the next one is a failover
+    assert_eq!(*failovers.0.lock().unwrap(), vec![down.clone()]);
+
+    // a job that is missing on one server can be on the server that failed
+    let transporter = transporter.with_region(&region);
+    let found = transporter.find(|server| transporter.get(format!("{}/unknown", server)));
+    assert!(found.await.is_err());
+    let only_up = RegionUri::new(Region::Custom(String::new()), "", vec![up.as_str()]);
+    let transporter = transporter.with_region(&only_up);
+    assert!(transporter.find(|server| transporter.get(format!("{}/unknown", server))).await.unwrap().is_none());
+
+    // only 404 means the job is missing, other statuses are errors
+    for status in ["401", "403", "503"] {
+        let found = transporter.find(|server| transporter.get(format!("{}/status/{}", server, status)));
+        assert!(found.await.unwrap_err().to_string().contains(status), "{}", status);
+    }
+    let failures = failovers.1.lock().unwrap();
+    assert_eq!(failures[failures.len() - 3..], ["rejected", "rejected", "http"]);
+}
+
+#[tokio::test]
+async fn test_find_parallel() {
+    use hyper::service::{make_service_fn, service_fn};
+
+    // a server that answers every request with the status after the delay
+    async fn spawn_server(status: StatusCode, delay: Duration) -> String {
+        let make_service = make_service_fn(move |_| async move {
+            Ok::<_, hyper::Error>(service_fn(move |_req: Request<Body>| async move {
+                tokio::time::sleep(delay).await;
+                Ok::<_, hyper::Error>(Response::builder().status(status).body(Body::empty()).unwrap())
+            }))
+        });
+        let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let addr = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+        addr
+    }
+
+    let holder = spawn_server(StatusCode::OK, Duration::ZERO).await;
+    let other = spawn_server(StatusCode::NOT_FOUND, Duration::from_millis(300)).await;
+    let region = RegionUri::new(Region::Custom(String::new()), "", vec![other.as_str(), holder.as_str()]);
+    let transporter = Transporter::new(Credentials::new("user", "password")).with_region(&region);
+
+    // the lookup returns with the first report, the slow server is not waited for
+    let started = Instant::now();
+    let found = transporter.find_parallel(|server| transporter.get(format!("{}/report", server)), false).await.unwrap();
+    assert_eq!(found.map(|r| r.status()), Some(StatusCode::OK));
+    assert!(started.elapsed() < Duration::from_millis(250));
+
+    // a delete waits until every server answered
+    let started = Instant::now();
+    let found = transporter.find_parallel(|server| transporter.delete(format!("{}/report", server)), true).await.unwrap();
+    assert!(found.is_some());
+    assert!(started.elapsed() >= Duration::from_millis(300));
+
+    let missing = RegionUri::new(Region::Custom(String::new()), "", vec![other.as_str()]);
+    let transporter = transporter.with_region(&missing);
+    assert!(transporter.find_parallel(|server| transporter.get(server), false).await.unwrap().is_none());
+
+    // a delete fails if a server could not delete the report, a lookup only needs the server that holds it
+    let broken = spawn_server(StatusCode::INTERNAL_SERVER_ERROR, Duration::ZERO).await;
+    let region = RegionUri::new(Region::Custom(String::new()), "", vec![holder.as_str(), broken.as_str()]);
+    let transporter = transporter.with_region(&region);
+    let delete = transporter.find_parallel(|server| transporter.delete(format!("{}/report", server)), true).await;
+    assert!(delete.unwrap_err().to_string().contains("500"));
+    assert!(transporter.find_parallel(|server| transporter.get(format!("{}/report", server)), false).await.unwrap().is_some());
+    let unauthorized = spawn_server(StatusCode::UNAUTHORIZED, Duration::ZERO).await;
+    let region = RegionUri::new(Region::Custom(String::new()), "", vec![other.as_str(), unauthorized.as_str()]);
+    let transporter = transporter.with_region(&region);
+    let lookup = transporter.find_parallel(|server| transporter.get(format!("{}/report", server)), false).await;
+    assert!(lookup.unwrap_err().to_string().contains("401"));
+
+    // the delete is also sent to a server with an open circuit, a lookup skips it and fails since the report can be on it
+    let down = format!("http://{}", std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap());
+    let config = TransportConfig { connect_retries: 0, circuit_failures: 1, ..TransportConfig::default() };
+    let region = RegionUri::new(Region::Custom(String::new()), "", vec![other.as_str(), down.as_str()]);
+    let transporter = Transporter::with_config(Credentials::new("user", "password"), config).with_region(&region);
+    assert!(transporter.find_parallel(|server| transporter.delete(format!("{}/report", server)), true).await.is_err());
+    assert_eq!(transporter.server_health()[1].state, super::health::CircuitState::Open);
+    let mut asked = vec![];
+    let delete = transporter.find_parallel(|server| {
+        asked.push(server.clone());
+        transporter.delete(format!("{}/report", server))
+    }, true);
+    assert!(delete.await.is_err());
+    assert_eq!(asked, vec![other.clone(), down.clone()]);
+    let lookup = transporter.find_parallel(|server| transporter.get(format!("{}/report", server)), false).await;
+    assert!(lookup.unwrap_err().to_string().contains(&down));
 }
@@ -1,4 +1,6 @@
 
+use std::str::FromStr;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Region {
     Europe,
@@ -8,6 +10,25 @@ pub enum Region {
     Custom(String)
 }
 
+/// Accepts the name or the country code of a region, e.g. "europe" or "de", and urls for a custom region.
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = s.trim();
+        if value.starts_with("http://") || value.starts_with("https://") {
+            return Ok(Region::Custom(value.trim_end_matches('/').to_string()));
+        }
+        match value.to_lowercase().as_str() {
+            "europe" | "eu" | "de" => Ok(Region::Europe),
+            "america" | "us" => Ok(Region::America),
+            "switzerland" | "ch" => Ok(Region::Switzerland),
+            "singapore" | "sg" => Ok(Region::Singapore),
+            _ => Err(format!("Unknown region '{}', use europe, america, switzerland, singapore or an url", value)),
+        }
+    }
+}
+
 pub fn determine_region_uri(region: Region) -> RegionUri {
     match region {
         Region::Europe => {
@@ -23,7 +44,35 @@ pub fn determine_region_uri(region: Region) -> RegionUri {
             RegionUri::new(Region::Singapore, "https://faxws.sg1.retarus.com", vec!["https://faxws.sg1.retarus.com"])
         }
         Region::Custom(data) => {
-            RegionUri::new(Region::Custom(String::new()), "", vec![data.as_str()])
+            RegionUri::new(Region::Custom(data.clone()), data.as_str(), vec![data.as_str()])
+        }
+    }
+}
+
+/// The sms service is Europe only: no endpoints in America, Switzerland or Singapore are documented, so None is returned for them.
+/// Use [Region::Custom] with the endpoint Retarus gave you for another region.
+pub fn determine_sms_region_uri(region: Region) -> Option<RegionUri> {
+    match region {
+        Region::Europe => {
+            Some(RegionUri::new(Region::Europe, "https://sms4a.eu.retarus.com", vec!["https://sms4a.de1.retarus.com", "https://sms4a.de2.retarus.com"]))
+        }
+        Region::America | Region::Switzerland | Region::Singapore => None,
+        Region::Custom(data) => {
+            Some(RegionUri::new(Region::Custom(data.clone()), data.as_str(), vec![data.as_str()]))
+        }
+    }
+}
+
+/// The webexpress portal is Europe only: no portals in America, Switzerland or Singapore are documented, so None is returned for them.
+/// Use [Region::Custom] with the portal Retarus gave you for another region.
+pub fn determine_webexpress_region_uri(region: Region) -> Option<RegionUri> {
+    match region {
+        Region::Europe => {
+            Some(RegionUri::new(Region::Europe, "https://webexpress.retarus.com", vec!["https://webexpress.retarus.com"]))
+        }
+        Region::America | Region::Switzerland | Region::Singapore => None,
+        Region::Custom(data) => {
+            Some(RegionUri::new(Region::Custom(data.clone()), data.as_str(), vec![data.as_str()]))
         }
     }
 }
@@ -46,4 +95,28 @@ impl RegionUri {
             servers:c
         }
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_service_regions() {
+    let uri = determine_sms_region_uri(Region::Europe).unwrap();
+    assert_eq!(uri.ha_addr, "https://sms4a.eu.retarus.com");
+    assert!(determine_sms_region_uri(Region::America).is_none());
+    assert!(determine_webexpress_region_uri(Region::Switzerland).is_none());
+
+    // the fax jobs are sent to the custom endpoint, not to a relative url
+    let fax = determine_region_uri(Region::Custom("http://localhost:8080".to_string()));
+    assert_eq!(fax.ha_addr, "http://localhost:8080");
+
+    let custom = determine_webexpress_region_uri(Region::Custom("http://localhost:8080".to_string())).unwrap();
+    assert_eq!(custom.ha_addr, "http://localhost:8080");
+    assert_eq!(custom.servers, vec!["http://localhost:8080".to_string()]);
+}
+
+#[test]
+fn test_parse_region() {
+    assert_eq!("Europe".parse::<Region>(), Ok(Region::Europe));
+    assert_eq!("ch".parse::<Region>(), Ok(Region::Switzerland));
+    assert_eq!("http://localhost:8080/".parse::<Region>(), Ok(Region::Custom("http://localhost:8080".to_string())));
+    assert!("mars".parse::<Region>().is_err());
+}
@@ -2,15 +2,28 @@ use crate::fax::job::Job;
 use crate::fax::report::{Report, ReportsAction};
 use crate::fax::responses::{BulkDelete, BulkGet, DeletedReport, FaxJobResponse};
 use crate::common::creds::Credentials;
-use crate::common::transport::{response_to_body, Transporter};
+use crate::common::build_error::{select_credentials, BuildError};
+use crate::common::health::ServerHealth;
+use crate::common::limit::{select_limiter, RateLimit};
+use crate::common::provider::CredentialProvider;
+use crate::common::metrics::{Metrics, NoMetrics};
+use crate::common::phone::redact;
+use crate::common::suppression::SuppressionList;
+use crate::common::transport::{response_to_body, HttpPool, TransportConfig, Transporter};
 use crate::common::uri::{determine_region_uri, Region, RegionUri};
 use hyper::{StatusCode};
 use std::error::Error;
+use std::sync::Arc;
 
+/// Clones share the connections, the rate limits and the server health of the client.
+#[derive(Clone)]
 pub struct ClientSDK {
     transporter: Transporter,
     region_uri: RegionUri,
     customer_number: String,
+    suppression_list: Option<SuppressionList>,
+    /// Ask all report servers at once in get_fax_report and delete_fax_report.
+    parallel_lookups: bool,
 }
 
 impl ClientSDK {
@@ -30,23 +43,40 @@ impl ClientSDK {
         ClientSDKBuilder::default()
     }
 
-    /// Send a fax to the Retarus server.
+    /// Send a fax to the Retarus server, recipients on the suppression list of the client are removed before.
+    #[tracing::instrument(skip_all, fields(service = "fax", operation = "send_job", recipients = job.recipient_count(), job_id = tracing::field::Empty))]
     pub async fn send_job(
         &self,
         customer_number: Option<String>,
-        job: Job,
+        mut job: Job,
     ) -> Result<FaxJobResponse, Box<dyn std::error::Error>> {
+        let suppressed = match &self.suppression_list {
+            Some(list) => job.suppress(list),
+            None => vec![],
+        };
+        if !suppressed.is_empty() {
+            let numbers: Vec<String> = suppressed.iter().map(|number| redact(number)).collect();
+            tracing::info!(suppressed = ?numbers, "recipients on the suppression list were removed");
+        }
+        if !job.has_recipients() && !suppressed.is_empty() {
+            return Err("All recipients of the job are on the suppression list".into());
+        }
         let mut cn = self.customer_number.clone();
         if customer_number.is_some() {
             cn = customer_number.unwrap();
         }
+        let transporter = self.transporter.for_account(&cn);
         let uri = format!("{}/rest/v1/{}/fax", &self.region_uri.ha_addr, cn);
-        let response = self.transporter.post::<Job>(uri, job).await?;
+        let response = transporter.post::<Job>(uri, job).await?;
         if response.status() == StatusCode::OK || response.status() == StatusCode::CREATED {
             let res_str = response_to_body(response).await?;
-            let a: FaxJobResponse = serde_json::from_str(res_str.as_str())?;
+            let mut a: FaxJobResponse = serde_json::from_str(res_str.as_str())?;
+            tracing::Span::current().record("job_id", a.job_id.as_str());
+            self.transporter.job_submitted();
+            a.suppressed = suppressed;
             Ok(a)
         } else {
+            self.transporter.job_rejected();
             let res_str = response_to_body(response).await?;
             Err(res_str.into())
         }
@@ -55,6 +85,7 @@ impl ClientSDK {
     /// Get the fax report for a specific job via the job_id that was returned from the send_job function.
     ///
     /// customer_number is optional and can be used if a job is from another customer_number, overrides the default.
+    #[tracing::instrument(skip_all, fields(service = "fax", operation = "get_fax_report", job_id = %job_id))]
     pub async fn get_fax_report(
         &self,
         job_id: String,
@@ -64,29 +95,26 @@ impl ClientSDK {
         if customer_number.is_some() {
             cn = customer_number.unwrap();
         }
-        for server in &self.region_uri.servers {
-            let uri = format!(
-                "{}/rest/v1/{}/fax/reports/{}",
-                server, cn, job_id
-            );
-            let response = self
-                .transporter
-                .get(uri)
-                .await?;
-        
-            if response.status() == StatusCode::OK {
+        let transporter = self.transporter.for_account(&cn);
+        let lookup = |server| transporter.get(format!("{}/rest/v1/{}/fax/reports/{}", server, cn, job_id));
+        let response = if self.parallel_lookups {
+            transporter.find_parallel(lookup, false).await?
+        } else {
+            transporter.find(lookup).await?
+        };
+        match response {
+            Some(response) => {
                 let res_str = response_to_body(response).await?;
                 let a: Report = serde_json::from_str(res_str.as_str())?;
-                return Ok(a);
-            }
-            if response.status() == StatusCode::NOT_FOUND {
-                continue;
+                self.record_recipients(std::slice::from_ref(&a));
+                Ok(a)
             }
+            None => Err("No Fax report was found, please try again".into()),
+        }
     }
-    Err("No Fax report was found, please try again".into())
-}
 
     /// Delete a single fax report with job_id.
+    #[tracing::instrument(skip_all, fields(service = "fax", operation = "delete_fax_report", job_id = %job_id))]
     pub async fn delete_fax_report(
         &self,
         job_id: String,
@@ -96,29 +124,26 @@ impl ClientSDK {
         if customer_number.is_some() {
             cn = customer_number.unwrap();
         }
-        for server in &self.region_uri.servers {
-            let uri = format!(
-                "{}/rest/v1/{}/fax/reports/{}",
-                server, cn, job_id
-            );
-            let response = self
-                .transporter
-                .delete(uri)
-                .await?;
-        
-            if response.status() == StatusCode::OK {
+        let transporter = self.transporter.for_account(&cn);
+        let delete = |server| transporter.delete(format!("{}/rest/v1/{}/fax/reports/{}", server, cn, job_id));
+        let response = if self.parallel_lookups {
+            // every delete is finished, a cancelled request could leave the report on its server
+            transporter.find_parallel(delete, true).await?
+        } else {
+            transporter.find(delete).await?
+        };
+        match response {
+            Some(response) => {
                 let res_str = response_to_body(response).await?;
                 let a: DeletedReport = serde_json::from_str(res_str.as_str())?;
-                return Ok(a);
+                Ok(a)
             }
-            if response.status() == StatusCode::NOT_FOUND {
-                continue;
-            }
-    }
-    Err("No Fax report was found, please try again".into())
+            None => Err("No Fax report was found, please try again".into()),
+        }
     }
 
     /// Fetches the last fax_reports, limit = 1000.
+    #[tracing::instrument(skip_all, fields(service = "fax", operation = "get_fax_reports"))]
     pub async fn get_fax_reports(
         &self,
         customer_number: Option<String>,
@@ -128,28 +153,24 @@ impl ClientSDK {
         if customer_number.is_some() {
             cn = customer_number.unwrap();
         }
-        for server in &self.region_uri.servers {
-            let uri = format!(
-                "{}/rest/v1/{}/fax/reports",
-                server,
-                cn
-            );
-            let response = self
-            .transporter
-            .get(uri)
+        let transporter = self.transporter.for_account(&cn);
+        let response = transporter
+            .find(|server| transporter.get(format!("{}/rest/v1/{}/fax/reports", server, cn)))
             .await?;
-
-            if response.status() == StatusCode::OK {
+        match response {
+            Some(response) => {
                 let res_str = response_to_body(response).await?;
                 let a: BulkGet = serde_json::from_str(res_str.as_str())?;
-                return Ok(a);
+                self.record_recipients(&a.reports);
+                Ok(a)
             }
+            None => Err("No report found!".into()),
         }
-        Err("No report found!".into())
         
     }
 
     /// Takes a vector of job_ids and deletes the corresponding report on the Retarus servers.
+    #[tracing::instrument(skip_all, fields(service = "fax", operation = "perform_bulk_delete", jobs = job_ids.len()))]
     pub async fn perform_bulk_delete(
         &self,
         job_ids: Vec<String>,
@@ -159,30 +180,26 @@ impl ClientSDK {
         if customer_number.is_some() {
             cn = customer_number.unwrap();
         }
+        let transporter = self.transporter.for_account(&cn);
         let payload = ReportsAction {
             action: "DELETE".to_string(),
             job_ids,
         };
-        for server in &self.region_uri.servers {
-            let uri = format!(
-                "{}/rest/v1/{}/fax/reports",
-                server,
-                cn
-            );
-        let response = self
-            .transporter
-            .post::<ReportsAction>(uri, payload.clone())
+        let response = transporter
+            .find(|server| transporter.post::<ReportsAction>(format!("{}/rest/v1/{}/fax/reports", server, cn), payload.clone()))
             .await?;
-            if response.status() == StatusCode::OK {
+        match response {
+            Some(response) => {
                 let res_str = response_to_body(response).await?;
                 let a: BulkDelete = serde_json::from_str(res_str.as_str())?;
-                return Ok(a);
-                }
+                Ok(a)
+            }
+            None => Err("No report found!".into()),
+        }
     }
-    Err("No report found!".into())
-}
 
     /// Takes a vector of job_ids and returns the report for these numbers. If no report is found, it will throw an error.
+    #[tracing::instrument(skip_all, fields(service = "fax", operation = "perform_bulk_get", jobs = job_ids.len()))]
     pub async fn perform_bulk_get(
         &self,
         job_ids: Vec<String>,
@@ -192,30 +209,27 @@ impl ClientSDK {
         if customer_number.is_some() {
             cn = customer_number.unwrap();
         }
+        let transporter = self.transporter.for_account(&cn);
         let payload = ReportsAction {
             action: "GET".to_string(),
             job_ids,
         };
-        for server in &self.region_uri.servers {
-            let uri = format!(
-                "{}/rest/v1/{}/fax/reports",
-                server,
-                cn
-            );
-        let response = self
-            .transporter
-            .post::<ReportsAction>(uri, payload.clone())
+        let response = transporter
+            .find(|server| transporter.post::<ReportsAction>(format!("{}/rest/v1/{}/fax/reports", server, cn), payload.clone()))
             .await?;
-        if response.status() == StatusCode::OK {
-            let res_str = response_to_body(response).await?;
-            let a: BulkGet = serde_json::from_str(res_str.as_str())?;
-            return Ok(a);
+        match response {
+            Some(response) => {
+                let res_str = response_to_body(response).await?;
+                let a: BulkGet = serde_json::from_str(res_str.as_str())?;
+                self.record_recipients(&a.reports);
+                Ok(a)
             }
+            None => Err("No report found!".into()),
+        }
     }
-    Err("No report found!".into())
-}
 
     /// Delete all reports (up to 1000 with one call). If you want to delete specific reports with the job_id, then use the [perform_bulk_delete] function
+    #[tracing::instrument(skip_all, fields(service = "fax", operation = "prune_reports"))]
     pub async fn prune_reports(
         &self,
         customer_number: Option<String>,
@@ -225,40 +239,62 @@ impl ClientSDK {
         if customer_number.is_some() {
             cn = customer_number.unwrap();
         }
-        for server in &self.region_uri.servers {
-            let uri = format!(
-                "{}/rest/v1/{}/fax/reports",
-                server,
-                cn
-            );
-            let response = self
-            .transporter
-            .delete(uri)
+        let transporter = self.transporter.for_account(&cn);
+        let response = transporter
+            .find(|server| transporter.delete(format!("{}/rest/v1/{}/fax/reports", server, cn)))
             .await?;
-
-            if response.status() == StatusCode::OK {
+        match response {
+            Some(response) => {
                 let res_str = response_to_body(response).await?;
                 let a: BulkDelete = serde_json::from_str(res_str.as_str())?;
-                return Ok(a);
+                Ok(a)
             }
+            None => Err("No report found!".into()),
         }
-        Err("No report found!".into())
         
     }
+
+    /// The circuit state and latency of each fax server of the region, the report servers are asked in this order of health.
+    pub fn server_health(&self) -> Vec<ServerHealth> {
+        self.transporter.server_health()
+    }
+
+    /// Count the final status of each recipient, recipients that are still processed are skipped.
+    fn record_recipients(&self, reports: &[Report]) {
+        for status in reports.iter().flat_map(|r| r.recipient_status.iter().flatten()) {
+            if !status.status.is_empty() && !status.status.eq_ignore_ascii_case("PENDING") {
+                self.transporter.metrics().fax_recipient(&status.status, &status.reason);
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ClientSDKBuilder {
     region: Region,
     credentials: Credentials,
+    credential_provider: Option<Arc<dyn CredentialProvider>>,
     customer_number: String,
+    suppression_list: Option<SuppressionList>,
+    parallel_lookups: bool,
+    transport_config: TransportConfig,
+    pool: Option<HttpPool>,
+    metrics: Arc<dyn Metrics>,
+    rate_limit: Option<RateLimit>,
 }
 impl<> ClientSDKBuilder {
     fn default() -> ClientSDKBuilder {
         ClientSDKBuilder {
             region: Region::Europe,
             credentials: Credentials::default(),
+            credential_provider: None,
             customer_number: "".to_string(),
+            suppression_list: None,
+            parallel_lookups: false,
+            transport_config: TransportConfig::default(),
+            pool: None,
+            metrics: Arc::new(NoMetrics),
+            rate_limit: None,
         }
     }
 
@@ -267,6 +303,12 @@ impl<> ClientSDKBuilder {
         self
     }
 
+    /// Ask the provider for the credentials of every request instead of using fixed credentials.
+    pub fn set_credential_provider<P: CredentialProvider + 'static>(mut self, provider: P) -> ClientSDKBuilder {
+        self.credential_provider = Some(Arc::new(provider));
+        self
+    }
+
     pub fn set_region(mut self, region: Region) -> ClientSDKBuilder {
         self.region = region;
         self
@@ -277,20 +319,86 @@ impl<> ClientSDKBuilder {
         self
     }
 
-    /// Build a new [ClientSDK] instance with the the arguments given to the builder.
+    /// Recipients on this list are removed from every job before it is sent.
+    pub fn set_suppression_list(mut self, list: SuppressionList) -> ClientSDKBuilder {
+        self.suppression_list = Some(list);
+        self
+    }
+
+    /// Ask all report servers of the region at once in get_fax_report and delete_fax_report instead of one after the other.
+    /// The first report that is found is returned and the other lookups are cancelled, deletes wait for all servers.
+    pub fn set_parallel_lookups(mut self, parallel: bool) -> ClientSDKBuilder {
+        self.parallel_lookups = parallel;
+        self
+    }
+
+    /// Timeouts, retries and connection pooling of the client.
+    pub fn set_transport_config(mut self, config: TransportConfig) -> ClientSDKBuilder {
+        self.transport_config = config;
+        self
+    }
+
+    /// Report the measurements of the client, e.g. to [PrometheusMetrics](crate::common::prometheus::PrometheusMetrics) with the `prometheus` feature.
+    pub fn set_metrics<M: Metrics + 'static>(mut self, metrics: M) -> ClientSDKBuilder {
+        self.metrics = Arc::new(metrics);
+        self
+    }
+
+    /// Limit the requests of each customer number the client and its clones call, they wait instead of failing.
+    pub fn set_rate_limit(mut self, limit: RateLimit) -> ClientSDKBuilder {
+        self.rate_limit = Some(limit);
+        self
+    }
+    /// Send the requests over the connection pool of another client, used by [Retarus](crate::Retarus).
+    pub(crate) fn set_pool(mut self, pool: HttpPool) -> ClientSDKBuilder {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Build a new [ClientSDK] instance with the the arguments given to the builder, panics if they are invalid, see [ClientSDKBuilder::try_build].
     pub fn build(self) -> ClientSDK {
-        assert!(self.credentials.password != String::new(), "You need to specify a password using the set_credentials argument_function.");
-        ClientSDK {
-            transporter: Transporter::new(self.credentials),
-            region_uri: determine_region_uri(self.region),
-            customer_number: self.customer_number,
+        match self.try_build() {
+            Ok(client) => client,
+            Err(err) => panic!("{}", err),
         }
     }
+
+    /// Build a new [ClientSDK] instance, fails if the credentials or the customer number are missing.
+    pub fn try_build(self) -> Result<ClientSDK, BuildError> {
+        let credentials = select_credentials("fax", self.credential_provider, self.credentials)?;
+        if self.customer_number.trim().is_empty() {
+            return Err(BuildError::MissingCustomerNumber);
+        }
+        let limiter = select_limiter("fax", self.rate_limit)?;
+        let pool = self.pool.unwrap_or_else(|| HttpPool::new(&self.transport_config));
+        let region_uri = determine_region_uri(self.region);
+        Ok(ClientSDK {
+            transporter: Transporter::with_pool(credentials, self.transport_config, pool)
+                .with_metrics("fax", self.metrics)
+                .with_limiter(limiter)
+                .with_region(&region_uri),
+            region_uri,
+            customer_number: self.customer_number,
+            suppression_list: self.suppression_list,
+            parallel_lookups: self.parallel_lookups,
+        })
+    }
 }
 
 #[test]
 fn test_build_client() {
     let creds = Credentials::new("abc", "password123");
-    let client = ClientSDKBuilder::default().set_credentials(creds);
+    let client = ClientSDKBuilder::default().set_credentials(creds).set_customer_number("12345".to_string());
     client.build();
 }
+
+#[test]
+fn test_try_build_client() {
+    let builder = || ClientSDKBuilder::default().set_customer_number("12345".to_string());
+    assert_eq!(builder().try_build().err(), Some(BuildError::PlaceholderCredentials("fax")));
+    assert_eq!(builder().set_credentials(Credentials::new("abc", "")).try_build().err(), Some(BuildError::MissingCredentials("fax")));
+    let creds = Credentials::new("abc", "password123");
+    let missing_number = ClientSDKBuilder::default().set_credentials(creds.clone()).set_customer_number(" ".to_string());
+    assert_eq!(missing_number.try_build().err(), Some(BuildError::MissingCustomerNumber));
+    assert!(builder().set_credentials(creds).try_build().is_ok());
+}
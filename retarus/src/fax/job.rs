@@ -1,3 +1,5 @@
+use crate::common::{phone::PhoneNumber, suppression::SuppressionList};
+
 use super::{document::Document};
 
 /// This represents a fax job that will be transmitted  to the Retarus server to send a fax.
@@ -31,6 +33,22 @@ impl Job {
     pub fn builder() -> JobBuilder {
         JobBuilder::default()
     }
+
+    /// Removes all recipients that are on the list and returns their numbers.
+    pub(crate) fn suppress(&mut self, list: &SuppressionList) -> Vec<String> {
+        let numbers = self.recipients.drain(..).map(|r| r.number).collect();
+        let (allowed, suppressed) = list.partition(numbers);
+        self.recipients = allowed.into_iter().map(|number| Number { number }).collect();
+        suppressed
+    }
+
+    pub(crate) fn has_recipients(&self) -> bool {
+        !self.recipients.is_empty()
+    }
+
+    pub(crate) fn recipient_count(&self) -> usize {
+        self.recipients.len()
+    }
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -77,6 +95,19 @@ impl JobBuilder {
         self
     }
 
+    /// Add a validated phone number, it is sent in E.164 format.
+    pub fn add_phone_number(mut self, number: PhoneNumber) -> JobBuilder {
+        self.recipients.push(Number { number: number.e164() });
+        self
+    }
+
+    pub fn add_phone_numbers(mut self, numbers: Vec<PhoneNumber>) -> JobBuilder {
+        for number in numbers.iter() {
+            self.recipients.push(Number { number: number.e164() });
+        }
+        self
+    }
+
     pub fn add_document(mut self, doc: Document) -> JobBuilder {
         self.documents.push(doc);
         self
@@ -105,4 +136,26 @@ fn test_job_builder() {
     .add_recipients(numbers)
     .build();
     assert_eq!(job.recipients.len(), 3)
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_job_builder_phone_numbers() {
+    let number = PhoneNumber::parse("0123 10000000", crate::common::phone::CountryCode::new(49).ok()).unwrap();
+    let job = JobBuilder::default()
+    .add_phone_number(number.clone())
+    .add_phone_numbers(vec![number])
+    .build();
+    assert_eq!(job.recipients.len(), 2);
+    assert_eq!(job.recipients[0].number, "+4912310000000")
+}
+
+#[test]
+fn test_job_suppression() {
+    let list = SuppressionList::new();
+    list.add("+4912310000000").unwrap();
+    let mut job = JobBuilder::default()
+    .add_recipients(vec!["4912310000000".to_string(), "+4917600000000".to_string()])
+    .build();
+    assert_eq!(job.suppress(&list), vec!["4912310000000".to_string()]);
+    assert!(job.has_recipients());
+}
@@ -7,7 +7,10 @@ use super::report::Report;
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FaxJobResponse{
-    pub job_id: String
+    pub job_id: String,
+    /// Recipients that were removed from the job, because they are on the suppression list of the client.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suppressed: Vec<String>
 }
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
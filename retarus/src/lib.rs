@@ -1,7 +1,11 @@
 pub mod common;
+pub mod client;
+pub use client::{Retarus, RetarusBuilder, ServiceConfig};
 #[cfg(feature = "sms")]
 pub mod sms;
 #[cfg(feature = "fax")]
 pub mod fax;
 #[cfg(feature = "webexpress")]
-pub mod webexpress;
\ No newline at end of file
+pub mod webexpress;
+#[cfg(feature = "blocking")]
+pub mod blocking;
\ No newline at end of file
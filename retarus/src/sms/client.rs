@@ -1,17 +1,24 @@
-use std::{error::Error};
+use std::{error::Error, sync::Arc};
 
 use hyper::StatusCode;
 
 use crate::{
     common::{
         creds::Credentials,
-        transport::{response_to_body, Transporter},
-        uri::{ Region, RegionUri},
+        build_error::{select_credentials, BuildError},
+        health::ServerHealth,
+        limit::{select_limiter, RateLimit},
+        metrics::{Metrics, NoMetrics},
+        phone::redact,
+        provider::CredentialProvider,
+        transport::{response_to_body, HttpPool, TransportConfig, Transporter},
+        suppression::SuppressionList,
+        uri::{determine_sms_region_uri, Region, RegionUri},
     },
-    sms::models::{SmsJob},
+    sms::models::{SmsFilter, SmsJob},
 };
 
-use super::models::{JobReport, JobResponse};
+use super::models::{BatchFailure, BatchResponse, JobReport, JobResponse, Message, Options};
 
 /// The official Retarus SMS SDK client, use the [SmsClientBuilder] function to configure an instance.
 /// 
@@ -25,55 +32,116 @@ use super::models::{JobReport, JobResponse};
 /// .expect("You need to export your credentials"))
 /// .build();
 /// ```
+///
+/// Clones share the connections, the rate limits and the server health of the client.
+#[derive(Clone)]
 pub struct SmsClient {
     transporter: Transporter,
     region_uri: RegionUri,
+    suppression_list: Option<SuppressionList>,
 }
 impl SmsClient {
     //! Create a builder instance of SmsClientBuilder, which you can use to configure from example: Set a specific region.
     pub fn builder() -> SmsClientBuilder {
         SmsClientBuilder {
             region: Region::Europe,
-            region_uris: vec![RegionUri::new(
-                Region::Europe,
-                "https://sms4a.eu.retarus.com",
-                vec![
-                    "https://sms4a.de1.retarus.com",
-                    "https://sms4a.de2.retarus.com",
-                ],
-            )],
             credentails: Credentials::new("", ""),
+            credential_provider: None,
+            suppression_list: None,
+            transport_config: TransportConfig::default(),
+            pool: None,
+            metrics: Arc::new(NoMetrics),
+            rate_limit: None,
         }
     }
 }
 impl SmsClient {
     /// Takes a SmsJob instance and send a sms according to the specified details to the Retarus servers to be processed.
-    /// Returns: the job_id
-    pub async fn send_sms(&self, job: SmsJob) -> Result<JobResponse, Box<dyn Error>> {
+    /// Recipients on the suppression list of the client are removed before the job is sent.
+    /// Returns: the job_id and the suppressed recipients
+    #[tracing::instrument(skip_all, fields(service = "sms", operation = "send_sms", recipients = job.recipients().len(), job_id = tracing::field::Empty))]
+    pub async fn send_sms(&self, mut job: SmsJob) -> Result<JobResponse, Box<dyn Error>> {
+        let suppressed = match &self.suppression_list {
+            Some(list) => job.suppress(list),
+            None => vec![],
+        };
+        if !suppressed.is_empty() {
+            let numbers: Vec<String> = suppressed.iter().map(|number| redact(number)).collect();
+            tracing::info!(suppressed = ?numbers, "recipients on the suppression list were removed");
+        }
+        if job.is_empty() && !suppressed.is_empty() {
+            return Err("All recipients of the job are on the suppression list".into());
+        }
         let uri = format!("{}/rest/v1/jobs", &self.region_uri.ha_addr);
         let res = self.transporter.post::<SmsJob>(uri, job).await?;
         if res.status() != StatusCode::OK && res.status() != StatusCode::CREATED {
+            self.transporter.job_rejected();
             let a = response_to_body(res).await?;
             return Err(a.into())
         }
         let a = response_to_body(res).await?;
-        let x: JobResponse = serde_json::from_str(a.as_str())?;
+        let mut x: JobResponse = serde_json::from_str(a.as_str())?;
+        tracing::Span::current().record("job_id", x.job_id.as_str());
+        self.transporter.job_submitted();
+        x.suppressed = suppressed;
         Ok(x)
     }
 
+    /// Takes any number of messages, splits them with [SmsJob::batch] into valid jobs and submits them one after another.
+    /// Returns: the job_ids of all submitted jobs, jobs that could not be submitted are returned with the reason in `failed`.
+    #[tracing::instrument(skip_all, fields(service = "sms", operation = "send_sms_batch", messages = messages.len()))]
+    pub async fn send_sms_batch(&self, options: Option<Options>, mut messages: Vec<Message>) -> BatchResponse {
+        let mut response = BatchResponse { job_ids: vec![], failed: vec![], suppressed: vec![] };
+        // suppress before batching, so no job is sent for recipients that are filtered out anyway
+        if let Some(list) = &self.suppression_list {
+            response.suppressed = messages.iter_mut().flat_map(|m| m.suppress(list)).collect();
+            messages.retain(Message::has_recipients);
+        }
+        for job in SmsJob::batch(options, messages) {
+            match self.send_sms(job.clone()).await {
+                Ok(res) => response.job_ids.push(res.job_id),
+                Err(err) => {
+                    tracing::warn!(error = %err, recipients = job.recipients().len(), "a job of the batch could not be sent");
+                    response.failed.push(BatchFailure { job, reason: err.to_string() })
+                }
+            }
+        }
+        response
+    }
+
     /// Get a specific job from the server
+    #[tracing::instrument(skip_all, fields(service = "sms", operation = "get_sms_job", job_id = %job_id))]
     pub async fn get_sms_job(&self, job_id: String) -> Result<JobReport, Box<dyn Error>> {
-        for server in &self.region_uri.servers {
-            let uri = format!("{}/rest/v1/jobs/{}", server, job_id);
-            let res = self.transporter.get(uri).await?;
-            if res.status() != StatusCode::OK {
-                continue;
+        let res = self.transporter.find(|server| self.transporter.get(format!("{}/rest/v1/jobs/{}", server, job_id))).await?;
+        match res {
+            Some(res) => {
+                let a = response_to_body(res).await?;
+                let x: JobReport = serde_json::from_str(a.as_str())?;
+                Ok(x)
             }
-            let a = response_to_body(res).await?;
-            let x: JobReport = serde_json::from_str(a.as_str())?;
-            return Ok(x);
+            None => Err("No report found!".into()),
         }
-        Err("No report found!".into())
+    }
+
+    /// Get the jobs that match the filter, with [SmsFilterBuilder::only_job_ids](super::models::SmsFilterBuilder::only_job_ids) only the job_id of each report is set.
+    #[tracing::instrument(skip_all, fields(service = "sms", operation = "get_sms_jobs"))]
+    pub async fn get_sms_jobs(&self, filter: SmsFilter) -> Result<Vec<JobReport>, Box<dyn Error>> {
+        let query = filter.create_filter_string();
+        let query = query.trim_end_matches(['&', '?']);
+        let res = self.transporter.find(|server| self.transporter.get(format!("{}/rest/v1/jobs{}", server, query))).await?;
+        match res {
+            Some(res) => {
+                let a = response_to_body(res).await?;
+                let x: Vec<JobReport> = serde_json::from_str(a.as_str())?;
+                Ok(x)
+            }
+            None => Err("No jobs found!".into()),
+        }
+    }
+
+    /// The circuit state and latency of each sms server of the region, the job servers are asked in this order of health.
+    pub fn server_health(&self) -> Vec<ServerHealth> {
+        self.transporter.server_health()
     }
 }
 
@@ -82,29 +150,99 @@ impl SmsClient {
 
 pub struct SmsClientBuilder {
     region: Region,
-    region_uris: Vec<RegionUri>,
     credentails: Credentials,
+    credential_provider: Option<Arc<dyn CredentialProvider>>,
+    suppression_list: Option<SuppressionList>,
+    transport_config: TransportConfig,
+    pool: Option<HttpPool>,
+    metrics: Arc<dyn Metrics>,
+    rate_limit: Option<RateLimit>,
 }
 impl SmsClientBuilder {
     pub fn set_credentials(mut self, credentails: Credentials) -> SmsClientBuilder {
         self.credentails = credentails;
         self
     }
+    /// Ask the provider for the credentials of every request instead of using fixed credentials.
+    pub fn set_credential_provider<P: CredentialProvider + 'static>(mut self, provider: P) -> SmsClientBuilder {
+        self.credential_provider = Some(Arc::new(provider));
+        self
+    }
+    /// The sms service of the SDK is Europe only, [Region::America], [Region::Switzerland] and [Region::Singapore] are rejected when the client is built.
+    /// Use [Region::Custom] with an endpoint Retarus gave you for another region.
     pub fn set_region(mut self, region: Region) -> SmsClientBuilder {
         self.region = region;
         self
     }
-    /// The builder function validates the given parameter and will return a instance of [SmsClient].
+    /// Recipients on this list are removed from every job before it is sent.
+    pub fn set_suppression_list(mut self, list: SuppressionList) -> SmsClientBuilder {
+        self.suppression_list = Some(list);
+        self
+    }
+    /// Timeouts, retries and connection pooling of the client.
+    pub fn set_transport_config(mut self, config: TransportConfig) -> SmsClientBuilder {
+        self.transport_config = config;
+        self
+    }
+    /// Report the measurements of the client, e.g. to [PrometheusMetrics](crate::common::prometheus::PrometheusMetrics) with the `prometheus` feature.
+    pub fn set_metrics<M: Metrics + 'static>(mut self, metrics: M) -> SmsClientBuilder {
+        self.metrics = Arc::new(metrics);
+        self
+    }
+    /// Limit the requests of each username of the client and its clones, they wait instead of failing.
+    pub fn set_rate_limit(mut self, limit: RateLimit) -> SmsClientBuilder {
+        self.rate_limit = Some(limit);
+        self
+    }
+    /// Send the requests over the connection pool of another client, used by [Retarus](crate::Retarus).
+    pub(crate) fn set_pool(mut self, pool: HttpPool) -> SmsClientBuilder {
+        self.pool = Some(pool);
+        self
+    }
+    /// The builder function validates the given parameter and will return a instance of [SmsClient], panics if they are invalid, see [SmsClientBuilder::try_build].
     pub fn build(self) -> SmsClient {
-        assert!(
-            self.credentails.password != "",
-            "Credentials must be set to use the sms client"
-        );
-        assert!(self.region == Region::Europe, "The sms service is currently only processed in the European region, select Region::Europe as your region.");
-
-        return SmsClient {
-            transporter: Transporter::new(self.credentails),
-            region_uri: self.region_uris.first().unwrap().to_owned()
+        match self.try_build() {
+            Ok(client) => client,
+            Err(err) => panic!("{}", err),
+        }
+    }
+    /// Validates the given parameter and returns an error instead of panicking.
+    pub fn try_build(self) -> Result<SmsClient, BuildError> {
+        let credentials = select_credentials("sms", self.credential_provider, self.credentails)?;
+        let region_uri = match determine_sms_region_uri(self.region.clone()) {
+            Some(region_uri) => region_uri,
+            None => return Err(BuildError::UnsupportedRegion { service: "sms", region: self.region }),
         };
+        let limiter = select_limiter("sms", self.rate_limit)?;
+        let pool = self.pool.unwrap_or_else(|| HttpPool::new(&self.transport_config));
+        Ok(SmsClient {
+            transporter: Transporter::with_pool(credentials, self.transport_config, pool)
+                .with_metrics("sms", self.metrics)
+                .with_limiter(limiter)
+                .with_region(&region_uri),
+            region_uri,
+            suppression_list: self.suppression_list,
+        })
     }
 }
+
+
+#[test]
+fn test_build_client_region() {
+    let client = SmsClient::builder()
+        .set_credentials(Credentials::new("abc", "password123"))
+        .set_region(Region::Custom("http://localhost:8080".to_string()))
+        .build();
+    assert_eq!(client.region_uri.ha_addr, "http://localhost:8080");
+}
+
+#[test]
+fn test_try_build_client() {
+    let missing = SmsClient::builder().try_build();
+    assert_eq!(missing.err(), Some(BuildError::MissingCredentials("sms")));
+    let singapore = SmsClient::builder()
+        .set_credentials(Credentials::new("abc", "password123"))
+        .set_region(Region::Singapore)
+        .try_build();
+    assert_eq!(singapore.err(), Some(BuildError::UnsupportedRegion { service: "sms", region: Region::Singapore }));
+}
@@ -0,0 +1,18 @@
+use std::fmt;
+
+use super::models::MAX_MESSAGES_PER_JOB;
+
+/// Returned by the [SmsJobBuilder](super::models::SmsJobBuilder) if a job would exceed the limits of a single sms job.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SmsJobError {
+    TooManyMessages,
+}
+impl fmt::Display for SmsJobError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SmsJobError::TooManyMessages => write!(f, "Too many messages, a job can only handle {} messages.", MAX_MESSAGES_PER_JOB),
+        }
+    }
+}
+
+impl std::error::Error for SmsJobError {}
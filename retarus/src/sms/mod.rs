@@ -1,2 +1,4 @@
 pub mod client;
-pub mod models;
\ No newline at end of file
+pub mod job_error;
+pub mod models;
+pub mod notification;
\ No newline at end of file
@@ -1,8 +1,21 @@
-use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::common::{phone::PhoneNumber, schedule::{optional_seconds, validate_start, Interval, RecurringWindow, ScheduleError}, suppression::SuppressionList};
+
+use super::job_error::SmsJobError;
+
+/// Maximum number of messages a single [SmsJob] can contain, the limit the builder of the SDK always enforced.
+pub const MAX_MESSAGES_PER_JOB: usize = 3;
+/// Maximum number of recipients, summed over all messages, [SmsJob::batch] puts into a single job.
+/// This is a limit of the SDK and not one documented by Retarus, it keeps the request bodies and the reports of a job small.
+/// Jobs built with the [SmsJobBuilder] are not limited.
+pub const MAX_RECIPIENTS_PER_JOB: usize = 1000;
 
 
 ///This object can be used to set more details about how the SmsJob should be processed.
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Options {
     /// src: Set your source number
@@ -26,17 +39,63 @@ pub struct Options {
     /// qos: Quality of Service. options: [ EXPRESS, NORMAL ]
     qos: Option<String>,
     /// job_period: Timestamp to schedule when to start processing the SMS Job (iso-8601).
-    job_period: Option<String>,
+    #[serde(default, with = "optional_seconds")]
+    job_period: Option<DateTime<Utc>>,
     /// duplicate_detection: bool
     duplicate_detection: Option<bool>,
     /// blackout_periods: Time periods in which no SMS is delivered (iso-8601). SMS will be scheduled to be sent at the end of the blackout period.
-    blackout_periods: Option<Vec<String>>,
+    blackout_periods: Option<Vec<Interval>>,
+}
+impl Options {
+    /// Request delivery notifications, they can be received with the [NotificationReceiver](super::notification::NotificationReceiver).
+    pub fn set_status_requested(mut self, status_requested: bool) -> Options {
+        self.status_requested = Some(status_requested);
+        self
+    }
+    pub fn set_customer_ref(mut self, customer_ref: String) -> Options {
+        self.customer_ref = Some(customer_ref);
+        self
+    }
+    /// Schedule when the job should be processed, fails if the timestamp is not in the future.
+    pub fn set_job_period(mut self, start: DateTime<Utc>) -> Result<Options, ScheduleError> {
+        self.job_period = Some(validate_start(start)?);
+        Ok(self)
+    }
+    pub fn add_blackout_period(mut self, period: Interval) -> Options {
+        self.blackout_periods.get_or_insert_with(Vec::new).push(period);
+        self
+    }
+    /// Add all occurrences of a recurring window between now and until as blackout periods.
+    pub fn add_blackout_window<Tz: chrono::TimeZone>(mut self, window: &RecurringWindow<Tz>, until: DateTime<Utc>) -> Options {
+        self.blackout_periods.get_or_insert_with(Vec::new).extend(window.expand(Utc::now(), until));
+        self
+    }
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct JobResponse {
-    pub job_id: String
+    pub job_id: String,
+    /// Recipients that were removed from the job, because they are on the suppression list of the client.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suppressed: Vec<String>
+}
+
+/// Returned by [SmsClient::send_sms_batch](super::client::SmsClient::send_sms_batch), contains the ids of all submitted jobs.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchResponse {
+    pub job_ids: Vec<String>,
+    pub failed: Vec<BatchFailure>,
+    pub suppressed: Vec<String>
+}
+
+/// A job of a batch that was rejected, can be resubmitted with [SmsClient::send_sms](super::client::SmsClient::send_sms).
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchFailure {
+    pub job: SmsJob,
+    pub reason: String
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -44,7 +103,25 @@ pub struct JobResponse {
 pub struct Recipient {
     dst: String,
     customer_ref: Option<String>,
-    blackout_periods: Option<String>,
+    blackout_periods: Option<Vec<Interval>>,
+}
+impl Recipient {
+    pub fn new(dst: String) -> Recipient {
+        Recipient { dst, customer_ref: None, blackout_periods: None }
+    }
+    pub fn from_phone_number(number: &PhoneNumber) -> Recipient {
+        Recipient::new(number.e164())
+    }
+    /// The customer_ref is returned in the delivery notification of this recipient.
+    pub fn set_customer_ref(mut self, customer_ref: String) -> Recipient {
+        self.customer_ref = Some(customer_ref);
+        self
+    }
+    /// Time periods in which this recipient should not receive the sms.
+    pub fn set_blackout_periods(mut self, periods: Vec<Interval>) -> Recipient {
+        self.blackout_periods = Some(periods);
+        self
+    }
 }
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -52,6 +129,27 @@ pub struct Message {
     text: String,
     recipients: Vec<Recipient>
 }
+impl Message {
+    /// Create a message with the given text that will be sent to every number in dst.
+    pub fn new(text: String, dst: Vec<String>) -> Message {
+        let recipients = dst.into_iter().map(Recipient::new).collect();
+        Message { text, recipients }
+    }
+    /// Create a message for recipients that were configured individually, e.g. with a customer_ref.
+    pub fn with_recipients(text: String, recipients: Vec<Recipient>) -> Message {
+        Message { text, recipients }
+    }
+    /// Removes all recipients that are on the list and returns their numbers.
+    pub(crate) fn suppress(&mut self, list: &SuppressionList) -> Vec<String> {
+        let (allowed, suppressed): (Vec<Recipient>, Vec<Recipient>) = self.recipients.drain(..).partition(|r| !list.contains(&r.dst));
+        self.recipients = allowed;
+        suppressed.into_iter().map(|r| r.dst).collect()
+    }
+
+    pub(crate) fn has_recipients(&self) -> bool {
+        !self.recipients.is_empty()
+    }
+}
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -67,6 +165,76 @@ impl SmsJob {
             messages: Vec::new(),
         }
     }
+
+    /// Removes all recipients that are on the list, messages without recipients are dropped. Returns the removed numbers.
+    pub(crate) fn suppress(&mut self, list: &SuppressionList) -> Vec<String> {
+        let suppressed = self.messages.iter_mut().flat_map(|m| m.suppress(list)).collect();
+        self.messages.retain(Message::has_recipients);
+        suppressed
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// The numbers of all recipients of the job, e.g. to report the recipients of a failed job.
+    pub fn recipients(&self) -> Vec<&str> {
+        self.messages.iter().flat_map(|m| m.recipients.iter().map(|r| r.dst.as_str())).collect()
+    }
+
+    /// Split any number of messages into jobs that respect [MAX_MESSAGES_PER_JOB] and [MAX_RECIPIENTS_PER_JOB],
+    /// messages without recipients are dropped and each job gets a copy of the options.
+    ///
+    /// No split can use less jobs than the lower bound of messages / 3 and recipients / 1000, both rounded up.
+    /// Each job is built so the remaining messages still fit into one job less than before: it completes the messages
+    /// the message limit requires, then it is filled with the largest messages, a message is split if it does not fit.
+    /// This reaches the lower bound for the usual batches, e.g. one message per recipient, or a few messages to many recipients.
+    /// A guaranteed minimum for every input is not computed, finding it is NP-hard (bin packing with splittable items and a limit of items per bin).
+    pub fn batch(options: Option<Options>, messages: Vec<Message>) -> Vec<SmsJob> {
+        let mut pending: Vec<Message> = messages.into_iter().filter(Message::has_recipients).collect();
+        pending.sort_by_key(|m| m.recipients.len());
+        let mut pending = VecDeque::from(pending);
+        let mut recipients: usize = pending.iter().map(|m| m.recipients.len()).sum();
+        let mut jobs = vec![];
+
+        while !pending.is_empty() {
+            let bound = pending.len().div_ceil(MAX_MESSAGES_PER_JOB).max(recipients.div_ceil(MAX_RECIPIENTS_PER_JOB));
+            // messages this job has to complete, so the others fit into bound - 1 jobs
+            let complete = pending.len().saturating_sub(MAX_MESSAGES_PER_JOB * (bound - 1));
+            let mut job: Vec<Message> = vec![];
+            let mut free = MAX_RECIPIENTS_PER_JOB;
+            if complete > 0 {
+                // the smallest messages, and the largest one that still fits to take as many recipients as possible
+                while job.len() + 1 < complete && pending.front().is_some_and(|m| m.recipients.len() <= free) {
+                    let message = pending.pop_front().expect("the front was checked");
+                    free -= message.recipients.len();
+                    job.push(message);
+                }
+                let fitting = pending.partition_point(|m| m.recipients.len() <= free);
+                if fitting > 0 {
+                    let message = pending.remove(fitting - 1).expect("the index is in range");
+                    free -= message.recipients.len();
+                    job.push(message);
+                }
+            }
+            while job.len() < MAX_MESSAGES_PER_JOB && free > 0 {
+                let mut message = match pending.pop_back() {
+                    Some(message) => message,
+                    None => break,
+                };
+                if message.recipients.len() > free {
+                    let rest = Message { text: message.text.clone(), recipients: message.recipients.split_off(free) };
+                    let position = pending.partition_point(|m| m.recipients.len() <= rest.recipients.len());
+                    pending.insert(position, rest);
+                }
+                free -= message.recipients.len();
+                job.push(message);
+            }
+            recipients -= MAX_RECIPIENTS_PER_JOB - free;
+            jobs.push(SmsJob { options: options.clone(), messages: job });
+        }
+        jobs
+    }
 }
 
 pub struct SmsJobBuilder {
@@ -74,24 +242,23 @@ pub struct SmsJobBuilder {
     messages: Vec<Message>
 }
 impl SmsJobBuilder {
-    pub fn add_message(mut self, message: String, dst: Vec<String>) -> SmsJobBuilder {
-        if self.messages.len() >= 3 {
-            panic!("Too many messages, a job can only handle 3 messages.")
-        }
-        let mut respt = vec![];
-        for number in dst.iter() {
-            respt.push(Recipient{ dst: number.to_owned(), customer_ref: None, blackout_periods: None });
-        }
-        let msg = Message { text: message, recipients: respt };
-        self.messages.push(msg);
-        self
+    /// Add a message that will be sent to all numbers in dst, fails if the job would exceed its limits.
+    pub fn add_message(self, message: String, dst: Vec<String>) -> Result<SmsJobBuilder, SmsJobError> {
+        self.add_messages(vec![Message::new(message, dst)])
+    }
+    /// Same as [SmsJobBuilder::add_message], but with validated phone numbers that are sent in E.164 format.
+    pub fn add_message_to(self, message: String, dst: Vec<PhoneNumber>) -> Result<SmsJobBuilder, SmsJobError> {
+        let recipients = dst.iter().map(Recipient::from_phone_number).collect();
+        self.add_messages(vec![Message::with_recipients(message, recipients)])
     }
-    pub fn add_messages(mut self, mut messages: Vec<Message>) -> SmsJobBuilder{
-        if self.messages.len() >= 3 || messages.len() >= 3 || messages.len() + self.messages.len() >= 3 {
-            panic!("Too many messages, a job can only handle 3 messages.")
+    /// Add several messages at once, fails without changing the job if it would then have more than [MAX_MESSAGES_PER_JOB] messages.
+    /// Use [SmsJob::batch] to split more messages into several jobs.
+    pub fn add_messages(mut self, mut messages: Vec<Message>) -> Result<SmsJobBuilder, SmsJobError> {
+        if self.messages.len() + messages.len() > MAX_MESSAGES_PER_JOB {
+            return Err(SmsJobError::TooManyMessages);
         }
         self.messages.append(&mut messages);
-        self
+        Ok(self)
     }
     pub fn configure_sms(mut self, options: Options) -> SmsJobBuilder {
         //! Override your default configuration set in the EAS portal how the sms will be processed.
@@ -140,6 +307,11 @@ impl SmsFilterBuilder{
         self.filter.to_ts = Some(ts);
         self
     }
+    /// Only jobs that are still processed, or only finished jobs with false.
+    pub fn set_open(mut self, open: bool) -> Self {
+        self.filter.open = Some(open);
+        self
+    }
     pub fn set_offset(mut self, offset: usize) -> Self {
         self.filter.offset = Some(offset);
         self
@@ -175,10 +347,10 @@ impl SmsFilter {
             query = format!("{}jobIdsOnly={}&", query,  &self.job_ids_only.unwrap())
         }
         if self.from_ts.is_some() {
-            query = format!("{}fromTs={}&", query,  &self.from_ts.unwrap())
+            query = format!("{}fromTs={}&", query,  &self.from_ts.unwrap().to_rfc3339_opts(SecondsFormat::Secs, true))
         }
         if self.to_ts.is_some() {
-            query = format!("{}toTs={}&", query,  &self.to_ts.unwrap())
+            query = format!("{}toTs={}&", query,  &self.to_ts.unwrap().to_rfc3339_opts(SecondsFormat::Secs, true))
         }
         if self.open.is_some() {
             query = format!("{}open={}&", query,  &self.open.unwrap())
@@ -191,4 +363,90 @@ impl SmsFilter {
         }
         query
     }
-}
\ No newline at end of file
+}
+
+
+#[test]
+fn test_add_messages_limit() {
+    let messages = vec![
+        Message::new("a".to_string(), vec!["490000000000".to_string()]),
+        Message::new("b".to_string(), vec!["490000000000".to_string()]),
+        Message::new("c".to_string(), vec!["490000000000".to_string()]),
+    ];
+    let builder = SmsJob::builder().add_messages(messages).unwrap();
+    let res = builder.add_message("d".to_string(), vec!["490000000000".to_string()]);
+    assert_eq!(res.err(), Some(SmsJobError::TooManyMessages));
+}
+
+#[test]
+fn test_add_message_without_recipient_limit() {
+    let dst = vec!["490000000000".to_string(); MAX_RECIPIENTS_PER_JOB + 1];
+    let job = SmsJob::builder().add_message("a".to_string(), dst).unwrap().build();
+    assert_eq!(job.recipients().len(), MAX_RECIPIENTS_PER_JOB + 1);
+}
+
+#[test]
+fn test_add_message_to() {
+    let number = PhoneNumber::parse("0049 123 10000000", None).unwrap();
+    let job = SmsJob::builder().add_message_to("a".to_string(), vec![number]).unwrap().build();
+    assert_eq!(job.messages[0].recipients[0].dst, "+4912310000000");
+}
+
+#[test]
+fn test_scheduled_options() {
+    use chrono::TimeZone;
+    let start = Utc.with_ymd_and_hms(2099, 11, 7, 19, 0, 0).unwrap() + chrono::Duration::nanoseconds(123_456_789);
+    let period = Interval::new(start, start + chrono::Duration::hours(2)).unwrap();
+    let options = Options::default().set_job_period(start).unwrap().add_blackout_period(period);
+    let json = serde_json::to_value(&options).unwrap();
+    assert_eq!(json["blackoutPeriods"][0], "2099-11-07T19:00:00Z/2099-11-07T21:00:00Z");
+    assert_eq!(json["jobPeriod"], "2099-11-07T19:00:00Z");
+    let parsed: Options = serde_json::from_value(json).unwrap();
+    assert_eq!(parsed.job_period, Some(Utc.with_ymd_and_hms(2099, 11, 7, 19, 0, 0).unwrap()));
+    assert_eq!(serde_json::to_value(Options::default()).unwrap()["jobPeriod"], serde_json::Value::Null);
+    assert!(Options::default().set_job_period(Utc::now()).is_err());
+}
+
+#[test]
+fn test_suppress_recipients() {
+    let list = SuppressionList::new();
+    list.add("+4912310000000").unwrap();
+    let mut job = SmsJob::builder()
+        .add_message("a".to_string(), vec!["004912310000000".to_string(), "+4917600000000".to_string()]).unwrap()
+        .add_message("b".to_string(), vec!["4912310000000".to_string()]).unwrap()
+        .build();
+    assert_eq!(job.suppress(&list).len(), 2);
+    assert_eq!(job.messages.len(), 1);
+    assert_eq!(job.messages[0].recipients[0].dst, "+4917600000000");
+}
+
+#[test]
+fn test_batch_jobs() {
+    let numbers: Vec<String> = (0..2500).map(|i| format!("49170{:07}", i)).collect();
+    let mut messages = vec![Message::new("large".to_string(), numbers)];
+    for i in 0..4 {
+        messages.push(Message::new(format!("small {}", i), vec!["490000000000".to_string()]));
+    }
+    let jobs = SmsJob::batch(None, messages);
+    for job in jobs.iter() {
+        assert!(job.messages.len() <= MAX_MESSAGES_PER_JOB);
+        assert!(job.messages.iter().map(|m| m.recipients.len()).sum::<usize>() <= MAX_RECIPIENTS_PER_JOB);
+    }
+    let total: usize = jobs.iter().flat_map(|j| j.messages.iter()).map(|m| m.recipients.len()).sum();
+    assert_eq!(total, 2504);
+
+    assert_eq!(jobs.len(), 3);
+
+    let messages = (0..7).map(|i| Message::new(format!("msg {}", i), vec!["490000000000".to_string()])).collect();
+    assert_eq!(SmsJob::batch(None, messages).len(), 3);
+
+    // filling the first job with the two large messages would need three jobs
+    let recipients = |count: usize| (0..count).map(|i| format!("49170{:07}", i)).collect::<Vec<String>>();
+    let mut messages: Vec<Message> = (0..4).map(|i| Message::new(format!("small {}", i), recipients(1))).collect();
+    messages.push(Message::new("large".to_string(), recipients(500)));
+    messages.push(Message::new("large".to_string(), recipients(500)));
+    messages.push(Message::new("nobody".to_string(), vec![]));
+    let jobs = SmsJob::batch(None, messages);
+    assert_eq!(jobs.len(), 2);
+    assert!(jobs.iter().flat_map(|j| j.messages.iter()).all(Message::has_recipients));
+}
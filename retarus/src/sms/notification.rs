@@ -0,0 +1,272 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    error::Error,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use futures::channel::{mpsc, oneshot};
+use hyper::{
+    body::HttpBody,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+
+/// Larger requests are rejected, a notification of a single recipient has less than 1 KiB.
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// A delivery notification pushed by the Retarus servers for every recipient of a job that was sent with `status_requested`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeliveryNotification {
+    pub job_id: String,
+    pub sms_id: Option<String>,
+    pub src: Option<String>,
+    pub dst: String,
+    /// status: Final status of the sms. options: [ OK, ERROR, EXPIRED, ... ]
+    pub status: String,
+    pub reason: Option<String>,
+    pub customer_ref: Option<String>,
+    pub sent_ts: Option<String>,
+    pub finished_ts: Option<String>,
+}
+
+/// The Retarus servers either push a single notification or a list of them.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum Payload {
+    Single(DeliveryNotification),
+    Multiple(Vec<DeliveryNotification>),
+}
+
+/// Parses the body of a notification request into a list of [DeliveryNotification].
+pub fn parse_notifications(body: &str) -> Result<Vec<DeliveryNotification>, serde_json::Error> {
+    match serde_json::from_str::<Payload>(body)? {
+        Payload::Single(notification) => Ok(vec![notification]),
+        Payload::Multiple(notifications) => Ok(notifications),
+    }
+}
+
+/// Stream of all notifications received after [NotificationReceiver::subscribe] was called.
+pub type NotificationStream = mpsc::UnboundedReceiver<DeliveryNotification>;
+
+type Callback = Arc<dyn Fn(&DeliveryNotification) + Send + Sync>;
+
+#[derive(Default)]
+struct Subscribers {
+    callbacks: Mutex<Vec<Callback>>,
+    streams: Mutex<Vec<mpsc::UnboundedSender<DeliveryNotification>>>,
+    customer_refs: Mutex<HashMap<String, Vec<Callback>>>,
+}
+impl Subscribers {
+    /// The callbacks are called after the locks are released, so they can register or remove callbacks themselves.
+    fn dispatch(&self, notification: DeliveryNotification) {
+        let mut callbacks: Vec<Callback> = self.callbacks.lock().unwrap().clone();
+        if let Some(customer_ref) = &notification.customer_ref {
+            if let Some(matching) = self.customer_refs.lock().unwrap().get(customer_ref) {
+                callbacks.extend(matching.iter().cloned());
+            }
+        }
+        for callback in callbacks {
+            callback(&notification);
+        }
+        self.streams
+            .lock()
+            .unwrap()
+            .retain(|sender| sender.unbounded_send(notification.clone()).is_ok());
+    }
+}
+
+/// An embeddable http server that receives the sms delivery notifications, configure the url of the receiver in the EAS portal.
+///
+/// ## Example
+/// ```ignore
+/// use futures::StreamExt;
+/// use retarus::sms::notification::NotificationReceiver;
+///
+/// let receiver = NotificationReceiver::new();
+/// receiver.on_customer_ref("order-4711", |n| println!("order 4711: {}", n.status));
+/// let mut notifications = receiver.subscribe();
+/// let handle = receiver.start("0.0.0.0:8080".parse().unwrap()).unwrap();
+///
+/// while let Some(notification) = notifications.next().await {
+///     println!("{:?}", notification);
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct NotificationReceiver {
+    subscribers: Arc<Subscribers>,
+}
+impl NotificationReceiver {
+    pub fn new() -> NotificationReceiver {
+        NotificationReceiver::default()
+    }
+
+    /// Register a callback that is called for every received notification.
+    pub fn on_notification<F>(&self, callback: F)
+    where
+        F: Fn(&DeliveryNotification) + Send + Sync + 'static,
+    {
+        self.subscribers.callbacks.lock().unwrap().push(Arc::new(callback));
+    }
+
+    /// Register a callback that is only called for notifications of recipients with the given customer_ref.
+    pub fn on_customer_ref<F>(&self, customer_ref: &str, callback: F)
+    where
+        F: Fn(&DeliveryNotification) + Send + Sync + 'static,
+    {
+        self.subscribers
+            .customer_refs
+            .lock()
+            .unwrap()
+            .entry(customer_ref.to_string())
+            .or_default()
+            .push(Arc::new(callback));
+    }
+
+    /// Remove all callbacks registered for the given customer_ref.
+    pub fn remove_customer_ref(&self, customer_ref: &str) {
+        self.subscribers.customer_refs.lock().unwrap().remove(customer_ref);
+    }
+
+    /// Get a [NotificationStream] of all notifications that are received from now on.
+    pub fn subscribe(&self) -> NotificationStream {
+        let (sender, receiver) = mpsc::unbounded();
+        self.subscribers.streams.lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// Bind the receiver to the given address and serve it on the current tokio runtime.
+    pub fn start(&self, addr: SocketAddr) -> Result<ReceiverHandle, Box<dyn Error>> {
+        let subscribers = self.subscribers.clone();
+        let make_svc = make_service_fn(move |_| {
+            let subscribers = subscribers.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| handle(subscribers.clone(), req)))
+            }
+        });
+        let (shutdown, signal) = oneshot::channel::<()>();
+        let server = Server::try_bind(&addr)?.serve(make_svc);
+        let local_addr = server.local_addr();
+        tokio::spawn(server.with_graceful_shutdown(async {
+            signal.await.ok();
+        }));
+        Ok(ReceiverHandle { local_addr, shutdown })
+    }
+}
+
+async fn handle(subscribers: Arc<Subscribers>, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    if req.method() != Method::POST {
+        return Ok(status_response(StatusCode::METHOD_NOT_ALLOWED));
+    }
+    let mut body = Vec::new();
+    let mut stream = req.into_body();
+    while let Some(chunk) = stream.data().await {
+        match chunk {
+            Ok(chunk) if body.len() + chunk.len() <= MAX_BODY_SIZE => body.extend_from_slice(&chunk),
+            Ok(_) => return Ok(status_response(StatusCode::PAYLOAD_TOO_LARGE)),
+            Err(_) => return Ok(status_response(StatusCode::BAD_REQUEST)),
+        }
+    }
+    let notifications = match std::str::from_utf8(&body).map(parse_notifications) {
+        Ok(Ok(notifications)) => notifications,
+        _ => return Ok(status_response(StatusCode::BAD_REQUEST)),
+    };
+    for notification in notifications {
+        subscribers.dispatch(notification);
+    }
+    Ok(status_response(StatusCode::OK))
+}
+
+fn status_response(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+/// Returned by [NotificationReceiver::start], keeps track of the running server.
+pub struct ReceiverHandle {
+    local_addr: SocketAddr,
+    shutdown: oneshot::Sender<()>,
+}
+impl ReceiverHandle {
+    /// The address the receiver is listening on, useful if it was bound to port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stop accepting notifications, requests that are in progress will be completed.
+    pub fn shutdown(self) {
+        self.shutdown.send(()).ok();
+    }
+}
+
+#[test]
+fn test_parse_notifications() {
+    let single = parse_notifications(include_str!("../../assets/sms_notification.json")).unwrap();
+    assert_eq!(single.len(), 1);
+    assert_eq!(single[0].customer_ref, Some("order-4711".to_string()));
+    let multiple = parse_notifications(&format!("[{}]", include_str!("../../assets/sms_notification.json"))).unwrap();
+    assert_eq!(multiple, single);
+    assert!(parse_notifications("{\"status\": \"OK\"}").is_err());
+}
+
+#[tokio::test]
+async fn test_receive_notification() {
+    use futures::StreamExt;
+
+    let receiver = NotificationReceiver::new();
+    let (sender, matched) = std::sync::mpsc::channel();
+    let sender = Mutex::new(sender);
+    receiver.on_customer_ref("order-4711", move |n| sender.lock().unwrap().send(n.job_id.clone()).unwrap());
+    let mut notifications = receiver.subscribe();
+    let handle = receiver.start("127.0.0.1:0".parse().unwrap()).unwrap();
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(format!("http://{}/", handle.local_addr()))
+        .header("Content-Type", "application/json")
+        .body(Body::from(include_str!("../../assets/sms_notification.json")))
+        .unwrap();
+    let res = hyper::Client::new().request(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let notification = notifications.next().await.unwrap();
+    assert_eq!(notification.status, "OK");
+    assert_eq!(matched.recv().unwrap(), notification.job_id);
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(format!("http://{}/", handle.local_addr()))
+        .body(Body::from("no json"))
+        .unwrap();
+    let res = hyper::Client::new().request(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(format!("http://{}/", handle.local_addr()))
+        .body(Body::from(vec![b' '; MAX_BODY_SIZE + 1]))
+        .unwrap();
+    let res = hyper::Client::new().request(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    handle.shutdown();
+}
+
+#[test]
+fn test_callback_registers_callback() {
+    let receiver = NotificationReceiver::new();
+    let inner = receiver.clone();
+    // registering from inside a callback deadlocked while the callbacks were called under the lock
+    receiver.on_notification(move |n| {
+        inner.on_notification(|_| {});
+        inner.remove_customer_ref(n.customer_ref.as_deref().unwrap_or_default());
+    });
+    receiver.on_customer_ref("order-4711", |_| {});
+    for notification in parse_notifications(include_str!("../../assets/sms_notification.json")).unwrap() {
+        receiver.subscribers.dispatch(notification);
+    }
+    assert_eq!(receiver.subscribers.callbacks.lock().unwrap().len(), 2);
+    assert!(receiver.subscribers.customer_refs.lock().unwrap().is_empty());
+}
@@ -1,65 +1,142 @@
-use std::{error::Error};
-use crate::common::{creds::Credentials, uri::{RegionUri, Region}, transport::Transporter};
-use super::job::WebexpressJob;
-
+use std::{error::Error, sync::Arc};
+use reqwest::multipart::Form;
+use crate::common::{creds::Credentials, build_error::{select_credentials, BuildError}, health::ServerHealth, limit::{select_limiter, RateLimit}, metrics::{Metrics, NoMetrics}, provider::CredentialProvider, uri::{determine_webexpress_region_uri, RegionUri, Region}, transport::{HttpPool, TransportConfig, Transporter}};
+use super::{import::{html_to_text, ImportResult}, job::WebexpressJob};
 
+/// Automation endpoint of the PicoPortal, it logs in with the j_username and j_password form fields.
+const LIST_IMPORT: &str = "/PicoPortal/autoLogin/listImport";
 
+/// Clones share the connections, the rate limits and the server health of the client.
+#[derive(Clone)]
 pub struct WebexpressClient {
     transporter: Transporter,
     region_uri: RegionUri,
-    credentials: Credentials,
 }
 impl WebexpressClient {
     pub fn builder() -> WebExpressBuilder {
         WebExpressBuilder {
             region: Region::Europe,
-            region_uris: vec![RegionUri::new(
-                Region::Europe,
-                "https://webexpress.retarus.com",
-                vec![""]
-            )],
             credentails: Credentials::new("", ""),
+            credential_provider: None,
+            transport_config: TransportConfig::default(),
+            pool: None,
+            metrics: Arc::new(NoMetrics),
+            rate_limit: None,
+        }
+    }
+    /// Import a distribution list, the returned [ImportResult] tells if the list was imported.
+    /// Returns an error if the portal answers with an error status.
+    #[tracing::instrument(skip_all, fields(service = "webexpress", operation = "upload_distributor_list"))]
+    pub async fn upload_distributor_list(&self, job: WebexpressJob) -> Result<ImportResult, Box<dyn Error>> {
+        let uri = format!("{}{}", &self.region_uri.ha_addr, LIST_IMPORT);
+        let res = self.transporter.form_post_with(uri, WebexpressClient::job_forms(job)).await?;
+        let status = res.status();
+        let x = res.text().await?;
+        if !status.is_success() {
+            self.transporter.job_rejected();
+            return Err(format!("The list import failed with status {}: {}", status, html_to_text(&x)).into());
+        }
+        Ok(self.record_import(ImportResult::parse(&x)))
+    }
+
+    /// The circuit state and latency of the webexpress portal of the region.
+    pub fn server_health(&self) -> Vec<ServerHealth> {
+        self.transporter.server_health()
+    }
+
+    fn record_import(&self, result: ImportResult) -> ImportResult {
+        if result.success {
+            self.transporter.job_submitted();
+        } else {
+            self.transporter.job_rejected();
         }
+        result
     }
-    pub async fn upload_distributor_list(&self, mut job: WebexpressJob) -> Result<String, Box<dyn Error>> {
-        let uri = format!("{}/PicoPortal/autoLogin/listImport", &self.region_uri.ha_addr);
-        
-        if &self.credentials.username != "" {
-            job.j_username = Some(self.credentials.username.to_string());
-            job.j_password = Some(self.credentials.password.to_string());
+
+    /// Creates the form of the job for every attempt, a list that is read from a reader or stream can only be sent once.
+    fn job_forms(job: WebexpressJob) -> impl FnMut(&Credentials) -> Result<Form, Box<dyn Error>> {
+        let mut job = Some(job);
+        move |credentials| {
+            let next = match job.as_ref().and_then(WebexpressJob::try_clone) {
+                Some(copy) => copy,
+                None => job.take().ok_or("The list can only be sent once")?,
+            };
+            Ok(next.into_form_with(credentials)?)
         }
-        let res = self.transporter.form_post(uri, job).await;
-        let x = res?.text().await?;
-        Ok(x)
     }
 }
 
 pub struct WebExpressBuilder {
     region: Region,
-    region_uris: Vec<RegionUri>,
     credentails: Credentials,
+    credential_provider: Option<Arc<dyn CredentialProvider>>,
+    transport_config: TransportConfig,
+    pool: Option<HttpPool>,
+    metrics: Arc<dyn Metrics>,
+    rate_limit: Option<RateLimit>,
 }
 impl WebExpressBuilder {
-    pub fn set_credentiale(mut self, credentails: Credentials) -> WebExpressBuilder {
+    pub fn set_credentials(mut self, credentails: Credentials) -> WebExpressBuilder {
         self.credentails = credentails;
         self
     }
+    #[deprecated(note = "use set_credentials, the same name as in the other builders")]
+    pub fn set_credentiale(self, credentails: Credentials) -> WebExpressBuilder {
+        self.set_credentials(credentails)
+    }
+    /// Ask the provider for the credentials of every request instead of using fixed credentials.
+    pub fn set_credential_provider<P: CredentialProvider + 'static>(mut self, provider: P) -> WebExpressBuilder {
+        self.credential_provider = Some(Arc::new(provider));
+        self
+    }
+    /// The webexpress portal of the SDK is Europe only, [Region::America], [Region::Switzerland] and [Region::Singapore] are rejected when the client is built.
+    /// Use [Region::Custom] with a portal Retarus gave you for another region.
     pub fn set_region(mut self, region: Region) -> WebExpressBuilder {
         self.region = region;
         self
     }
-    /// The builder function validates the given parameter and will return a instance of [WebexpressClient].
+    /// Timeouts, retries and connection pooling of the client, uploads use the same settings.
+    pub fn set_transport_config(mut self, config: TransportConfig) -> WebExpressBuilder {
+        self.transport_config = config;
+        self
+    }
+    /// Report the measurements of the client, e.g. to [PrometheusMetrics](crate::common::prometheus::PrometheusMetrics) with the `prometheus` feature.
+    pub fn set_metrics<M: Metrics + 'static>(mut self, metrics: M) -> WebExpressBuilder {
+        self.metrics = Arc::new(metrics);
+        self
+    }
+    /// Limit the requests of each username of the client and its clones, they wait instead of failing.
+    pub fn set_rate_limit(mut self, limit: RateLimit) -> WebExpressBuilder {
+        self.rate_limit = Some(limit);
+        self
+    }
+    /// Send the requests over the connection pool of another client, used by [Retarus](crate::Retarus).
+    pub(crate) fn set_pool(mut self, pool: HttpPool) -> WebExpressBuilder {
+        self.pool = Some(pool);
+        self
+    }
+    /// The builder function validates the given parameter and will return a instance of [WebexpressClient], panics if they are invalid, see [WebExpressBuilder::try_build].
     pub fn build(self) -> WebexpressClient {
-        assert!(
-            self.credentails.password != "",
-            "Credentials must be set to use the webexpress client"
-        );
-        assert!(self.region == Region::Europe, "The webexpress service is currently only processed in the European region, select Region::Europe as your region.");
-
-        return WebexpressClient {
-            transporter: Transporter::new(self.credentails.clone()),
-            region_uri: self.region_uris.first().unwrap().to_owned(),
-            credentials: self.credentails
+        match self.try_build() {
+            Ok(client) => client,
+            Err(err) => panic!("{}", err),
+        }
+    }
+    /// Validates the given parameter and returns an error instead of panicking.
+    pub fn try_build(self) -> Result<WebexpressClient, BuildError> {
+        let credentials = select_credentials("webexpress", self.credential_provider, self.credentails)?;
+        let region_uri = match determine_webexpress_region_uri(self.region.clone()) {
+            Some(region_uri) => region_uri,
+            None => return Err(BuildError::UnsupportedRegion { service: "webexpress", region: self.region }),
         };
+        let limiter = select_limiter("webexpress", self.rate_limit)?;
+        let pool = self.pool.unwrap_or_else(|| HttpPool::new(&self.transport_config));
+        Ok(WebexpressClient {
+            transporter: Transporter::with_pool(credentials, self.transport_config, pool)
+                .with_metrics("webexpress", self.metrics)
+                .with_limiter(limiter)
+                .with_region(&region_uri),
+            region_uri,
+        })
     }
 }
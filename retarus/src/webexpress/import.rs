@@ -0,0 +1,70 @@
+/// The message the portal shows after a list was imported, the only response of the import that is documented.
+const SUCCESS_MESSAGE: &str = "the list has been successfully imported";
+
+/// The result of a list import, parsed from the page that is returned by the portal.
+///
+/// The counts of imported and rejected rows, the reasons per row and the id of the list are not part of the result:
+/// the format of the import report is not documented, only its success message is. Until Retarus documents the report,
+/// [message](ImportResult::message) holds the text of the page and the rows can be checked before the upload with
+/// [validate_list](super::list::validate_list).
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ImportResult {
+    pub success: bool,
+    /// The text of the page without markup, it contains the reason if the list was not imported.
+    pub message: String,
+}
+impl ImportResult {
+    /// Parse the html or text body that is returned by the list import.
+    /// The import succeeded if a line of the page is the success message of the portal.
+    pub fn parse(body: &str) -> ImportResult {
+        let message = html_to_text(body);
+        let success = message
+            .lines()
+            .any(|line| line.trim_end_matches(['.', '!']).trim().eq_ignore_ascii_case(SUCCESS_MESSAGE));
+        ImportResult { success, message }
+    }
+}
+
+/// Removes all tags, every block element starts a new line. Empty lines are dropped.
+pub(crate) fn html_to_text(body: &str) -> String {
+    let mut text = String::with_capacity(body.len());
+    let mut rest = body;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let end = match rest[start..].find('>') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let tag = rest[start + 1..end].trim_start_matches('/').to_lowercase();
+        let name = tag.split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or("");
+        if ["br", "p", "div", "li", "tr", "title", "h1", "h2", "h3", "ul", "table"].contains(&name) {
+            text.push('\n');
+        }
+        rest = &rest[end + 1..];
+    }
+    if !rest.contains('<') {
+        text.push_str(rest);
+    }
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&");
+    text.lines().map(str::trim).filter(|l| !l.is_empty()).collect::<Vec<_>>().join("\n")
+}
+
+#[test]
+fn test_parse_import() {
+    let result = ImportResult::parse("The list has been successfully imported");
+    assert!(result.success);
+    let result = ImportResult::parse("<html><body><p>The list has been successfully imported.</p></body></html>");
+    assert!(result.success);
+    assert_eq!(result.message, "The list has been successfully imported.");
+
+    // only the whole message counts, not a page that contains it
+    assert!(!ImportResult::parse("The list has not been successfully imported").success);
+    assert!(!ImportResult::parse("<p>Why was the list not successfully imported?</p>").success);
+    assert!(!ImportResult::parse("<html><head><title>Login</title></head></html>").success);
+}
@@ -1,7 +1,111 @@
 
-use std::{path::PathBuf, io::{Read}, fs::File};
-use reqwest::{multipart};
+use std::{convert::TryFrom, error::Error, fmt, path::PathBuf, io::{Read}};
+use hyper::body::Bytes;
+use reqwest::{multipart, Body};
 
+use zeroize::Zeroizing;
+
+use crate::common::{creds::{Credentials, Secret}, phone::CountryCode};
+
+use super::job_error::WebexpressJobError;
+
+/// Which kind of list is imported, only the values the portal is known to accept are offered.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[non_exhaustive]
+pub enum ListType {
+    #[default]
+    DistributionList,
+}
+impl ListType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ListType::DistributionList => "distributionlist",
+        }
+    }
+}
+
+/// Who can see the imported list in the portal, only the values the portal is known to accept are offered.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[non_exhaustive]
+pub enum Visibility {
+    #[default]
+    Company,
+}
+impl Visibility {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Visibility::Company => "company",
+        }
+    }
+}
+
+/// The charset of the uploaded file, only the values the portal is known to accept are offered.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[non_exhaustive]
+pub enum Charset {
+    #[default]
+    Utf8,
+}
+impl Charset {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Charset::Utf8 => "utf-8",
+        }
+    }
+}
+
+/// Where the content of the list is read from when the job is uploaded.
+pub enum ListSource {
+    Path(PathBuf),
+    Bytes { file_name: String, data: Vec<u8> },
+    Reader { file_name: String, reader: Box<dyn Read + Send> },
+    Stream { file_name: String, body: Body },
+}
+impl ListSource {
+    /// Paths and bytes can be sent again, readers and streams are consumed by the first upload.
+    pub(crate) fn try_clone(&self) -> Option<ListSource> {
+        match self {
+            ListSource::Path(path) => Some(ListSource::Path(path.clone())),
+            ListSource::Bytes { file_name, data } => Some(ListSource::Bytes { file_name: file_name.clone(), data: data.clone() }),
+            ListSource::Reader { .. } | ListSource::Stream { .. } => None,
+        }
+    }
+
+    fn into_part(self) -> Result<multipart::Part, WebexpressJobError> {
+        let (file_name, part) = match self {
+            ListSource::Path(path) => {
+                let data = std::fs::read(&path)
+                    .map_err(|err| WebexpressJobError::FileNotReadable(format!("{}: {}", path.display(), err)))?;
+                let file_name = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .ok_or_else(|| WebexpressJobError::FileNotReadable(format!("{}: invalid file name", path.display())))?
+                    .to_string();
+                (file_name, multipart::Part::bytes(data))
+            }
+            ListSource::Bytes { file_name, data } => (file_name, multipart::Part::bytes(data)),
+            ListSource::Reader { file_name, mut reader } => {
+                let mut data = vec![];
+                reader
+                    .read_to_end(&mut data)
+                    .map_err(|err| WebexpressJobError::FileNotReadable(format!("{}: {}", file_name, err)))?;
+                (file_name, multipart::Part::bytes(data))
+            }
+            ListSource::Stream { file_name, body } => (file_name, multipart::Part::stream(body)),
+        };
+        Ok(part.file_name(file_name).mime_str("text/csv").expect("text/csv is a valid mime type"))
+    }
+}
+impl fmt::Debug for ListSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ListSource::Path(path) => write!(f, "Path({:?})", path),
+            ListSource::Bytes { file_name, data } => write!(f, "Bytes({:?}, {} bytes)", file_name, data.len()),
+            ListSource::Reader { file_name, .. } => write!(f, "Reader({:?})", file_name),
+            ListSource::Stream { file_name, .. } => write!(f, "Stream({:?})", file_name),
+        }
+    }
+}
 
 pub struct WebexpressJob {
     pub j_username: Option<String>,
@@ -9,84 +113,298 @@ pub struct WebexpressJob {
     dlu_listname: String,
     dlu_listcomment: String,
     dlu_type: String,
-    dlu_file: String,
+    dlu_file: ListSource,
     dlu_charset: String,
-    dlu_visibility: Option<String>,
+    dlu_visibility: Visibility,
     dlu_defaultcountrycode: String,
-    dlu_firstrowcolumnnames: String
+    dlu_firstrowcolumnnames: bool
 }
 impl WebexpressJob {
+    /// Create a job to import a list, set at least the list name, the file and the default country code.
+    ///
+    /// ## Example
+    /// ```rust
+    /// use retarus::webexpress::job::{Charset, WebexpressJob};
+    ///
+    /// let job = WebexpressJob::builder()
+    ///     .set_list_name("RetarusCustomerTest".to_string())
+    ///     .set_list_comment("Customers with a high order volume".to_string())
+    ///     .set_file("assets/test.csv")
+    ///     .set_charset(Charset::Utf8)
+    ///     .set_default_country_code("+49".parse().unwrap())
+    ///     .build()
+    ///     .unwrap();
+    /// ```
     pub fn builder() -> WebexpressJobBuilder {
         WebexpressJobBuilder::default()
     }
-    
+
     /// Create a minimal job with all the needed information to send to the server.
+    #[deprecated(note = "use WebexpressJob::builder, it validates the job")]
     pub fn minimal(listname: String, listcomment: String, list_type: String, file_path: String, charset: String, default_country_code: String) -> WebexpressJob {
-         WebexpressJob {
+        WebexpressJob {
             j_username: None,
             j_password: None,
             dlu_listname: listname,
             dlu_listcomment: listcomment,
             dlu_type: list_type,
-            dlu_file: file_path,
+            dlu_file: ListSource::Path(file_path.into()),
             dlu_charset: charset,
-            dlu_visibility: None,
+            dlu_visibility: Visibility::Company,
             dlu_defaultcountrycode: default_country_code,
-            dlu_firstrowcolumnnames: "on".to_string()
+            dlu_firstrowcolumnnames: true,
         }
     }
+
+    /// A copy to repeat the upload, None if the list is read from a reader or stream.
+    pub(crate) fn try_clone(&self) -> Option<WebexpressJob> {
+        Some(WebexpressJob {
+            j_username: self.j_username.clone(),
+            j_password: self.j_password.clone(),
+            dlu_listname: self.dlu_listname.clone(),
+            dlu_listcomment: self.dlu_listcomment.clone(),
+            dlu_type: self.dlu_type.clone(),
+            dlu_file: self.dlu_file.try_clone()?,
+            dlu_charset: self.dlu_charset.clone(),
+            dlu_visibility: self.dlu_visibility,
+            dlu_defaultcountrycode: self.dlu_defaultcountrycode.clone(),
+            dlu_firstrowcolumnnames: self.dlu_firstrowcolumnnames,
+        })
+    }
 }
-impl From<WebexpressJob> for multipart::Form {
-    fn from(item: WebexpressJob) -> multipart::Form {
-        let file_path = PathBuf::from(&item.dlu_file);
-        let mut file = File::open(&file_path).unwrap();
+impl TryFrom<WebexpressJob> for multipart::Form {
+    type Error = WebexpressJobError;
 
+    fn try_from(mut item: WebexpressJob) -> Result<multipart::Form, WebexpressJobError> {
+        let password = item.j_password.take().ok_or(WebexpressJobError::MissingCredentials)?;
+        item.into_form(multipart::Part::text(password))
+    }
+}
+impl WebexpressJob {
+    /// The form with the given credentials as form fields, or with those of the job if the credentials are empty.
+    /// The password is not copied into a string, its part is overwritten with zeros when the form is dropped.
+    pub(crate) fn into_form_with(mut self, credentials: &Credentials) -> Result<multipart::Form, WebexpressJobError> {
+        if credentials.username().is_empty() {
+            return self.try_into();
+        }
+        self.j_username = Some(credentials.username().to_string());
+        self.j_password = None;
+        self.into_form(secret_part(credentials.password()))
+    }
+
+    fn into_form(self, password: multipart::Part) -> Result<multipart::Form, WebexpressJobError> {
+        let username = self.j_username.ok_or(WebexpressJobError::MissingCredentials)?;
+        let file_part = self.dlu_file.into_part()?;
 
-        let mut buffer = vec![0; file_path.metadata().unwrap().len() as usize];
-        file.read_exact(&mut buffer).expect("buffer overflow");
-        println!("File length: {:?}", buffer.len());
-        let filename = file_path.file_name().unwrap().to_str().unwrap().to_string();
-        let file_part = multipart::Part::bytes(buffer).file_name(filename).mime_str("text/csv").unwrap();
-        
-        reqwest::multipart::Form::new()
-        .text("j_username", item.j_username.unwrap())
-        .text("j_password", item.j_password.unwrap())
-        .text("dlu_listcomment", item.dlu_listcomment)
+        let form = reqwest::multipart::Form::new()
+        .text("j_username", username)
+        .part("j_password", password)
+        .text("dlu_listcomment", self.dlu_listcomment)
         .part("dlu_file", file_part)
-        .text("dlu_listname", item.dlu_listname)
-        .text("dlu_type", item.dlu_type)
-        .text("dlu_charset", item.dlu_charset)
-        .text("dlu_visibility", "company".to_string())
-        .text("dlu_defaultcountrycode", item.dlu_defaultcountrycode)
-        .text("dlu_firstrowcolumnnames", item.dlu_firstrowcolumnnames)
+        .text("dlu_listname", self.dlu_listname)
+        .text("dlu_type", self.dlu_type)
+        .text("dlu_charset", self.dlu_charset)
+        .text("dlu_visibility", self.dlu_visibility.as_str())
+        .text("dlu_defaultcountrycode", self.dlu_defaultcountrycode);
+        // an unchecked checkbox is not sent by the portal form
+        Ok(if self.dlu_firstrowcolumnnames { form.text("dlu_firstrowcolumnnames", "on") } else { form })
     }
-} 
+}
+
+/// A part with the value of the secret, its bytes are overwritten with zeros when the part is dropped.
+fn secret_part(secret: &Secret) -> multipart::Part {
+    let value = Zeroizing::new(secret.expose().as_bytes().to_vec());
+    let length = value.len() as u64;
+    multipart::Part::stream_with_length(bytes::Bytes::from_owner(value), length)
+}
 
 pub struct WebexpressJobBuilder {
-    j_username: Option<String>,
-    j_password: Option<String>,
     dlu_listname: String,
     dlu_listcomment: String,
-    dlu_type: String,
-    dlu_file: String,
-    dlu_charset: String,
-    dlu_visibility: Option<String>,
-    dlu_defaultcountrycode: String,
-    dlu_firstrowcolumnnames: String
+    dlu_type: ListType,
+    dlu_file: Option<ListSource>,
+    dlu_charset: Charset,
+    dlu_visibility: Visibility,
+    dlu_defaultcountrycode: Option<CountryCode>,
+    dlu_firstrowcolumnnames: bool,
 }
 impl WebexpressJobBuilder {
     pub fn default() -> WebexpressJobBuilder {
         WebexpressJobBuilder{
-            j_username: None,
-            j_password: None,
             dlu_listname: "".to_string(),
             dlu_listcomment: "".to_string(),
-            dlu_type: "".to_string(),
-            dlu_file: "".to_string(),
-            dlu_charset: "".to_string(),
-            dlu_visibility: None,
-            dlu_defaultcountrycode: "".to_string(),
-            dlu_firstrowcolumnnames: "".to_string(),
+            dlu_type: ListType::default(),
+            dlu_file: None,
+            dlu_charset: Charset::default(),
+            dlu_visibility: Visibility::default(),
+            dlu_defaultcountrycode: None,
+            dlu_firstrowcolumnnames: true,
+        }
+    }
+
+    /// The name under which the list is shown in the portal.
+    pub fn set_list_name(mut self, name: String) -> WebexpressJobBuilder {
+        self.dlu_listname = name;
+        self
+    }
+
+    pub fn set_list_comment(mut self, comment: String) -> WebexpressJobBuilder {
+        self.dlu_listcomment = comment;
+        self
+    }
+
+    pub fn set_list_type(mut self, list_type: ListType) -> WebexpressJobBuilder {
+        self.dlu_type = list_type;
+        self
+    }
+
+    /// Path to the csv file that contains the list, the file is read when the job is uploaded.
+    pub fn set_file<P: Into<PathBuf>>(mut self, path: P) -> WebexpressJobBuilder {
+        self.dlu_file = Some(ListSource::Path(path.into()));
+        self
+    }
+
+    /// Upload a list that was generated in memory, file_name is the name shown in the portal.
+    pub fn set_content(mut self, file_name: String, data: Vec<u8>) -> WebexpressJobBuilder {
+        self.dlu_file = Some(ListSource::Bytes { file_name, data });
+        self
+    }
+
+    /// The reader is read to the end when the job is uploaded.
+    pub fn set_reader<R: Read + Send + 'static>(mut self, file_name: String, reader: R) -> WebexpressJobBuilder {
+        self.dlu_file = Some(ListSource::Reader { file_name, reader: Box::new(reader) });
+        self
+    }
+
+    /// Stream the list to the server without loading it into memory.
+    pub fn set_stream<S>(mut self, file_name: String, stream: S) -> WebexpressJobBuilder
+    where
+        S: futures::TryStream + Send + 'static,
+        S::Error: Into<Box<dyn Error + Send + Sync>>,
+        Bytes: From<S::Ok>,
+    {
+        self.dlu_file = Some(ListSource::Stream { file_name, body: Body::wrap_stream(stream) });
+        self
+    }
+
+    pub fn set_charset(mut self, charset: Charset) -> WebexpressJobBuilder {
+        self.dlu_charset = charset;
+        self
+    }
+
+    pub fn set_visibility(mut self, visibility: Visibility) -> WebexpressJobBuilder {
+        self.dlu_visibility = visibility;
+        self
+    }
+
+    /// Used for numbers in the list that are in national format.
+    pub fn set_default_country_code(mut self, code: CountryCode) -> WebexpressJobBuilder {
+        self.dlu_defaultcountrycode = Some(code);
+        self
+    }
+
+    /// Set to false if the first row of the file contains data instead of the column names, default: true.
+    pub fn set_first_row_column_names(mut self, first_row_column_names: bool) -> WebexpressJobBuilder {
+        self.dlu_firstrowcolumnnames = first_row_column_names;
+        self
+    }
+
+    /// Validates the given arguments and builds the job.
+    pub fn build(self) -> Result<WebexpressJob, WebexpressJobError> {
+        if self.dlu_listname.trim().is_empty() {
+            return Err(WebexpressJobError::MissingListName);
         }
+        Ok(WebexpressJob {
+            j_username: None,
+            j_password: None,
+            dlu_listname: self.dlu_listname,
+            dlu_listcomment: self.dlu_listcomment,
+            dlu_type: self.dlu_type.as_str().to_string(),
+            dlu_file: self.dlu_file.ok_or(WebexpressJobError::MissingFile)?,
+            dlu_charset: self.dlu_charset.as_str().to_string(),
+            dlu_visibility: self.dlu_visibility,
+            dlu_defaultcountrycode: self.dlu_defaultcountrycode.ok_or(WebexpressJobError::MissingCountryCode)?.to_string(),
+            dlu_firstrowcolumnnames: self.dlu_firstrowcolumnnames,
+        })
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_job_to_form() {
+    let builder = || {
+        WebexpressJob::builder()
+            .set_list_name("Customers".to_string())
+            .set_default_country_code(CountryCode::new(49).unwrap())
+    };
+    let mut job = builder().set_content("customers.csv".to_string(), b"name;fax\nRetarus;+498955280\n".to_vec()).build().unwrap();
+    job.j_username = Some("user".to_string());
+    job.j_password = Some("password".to_string());
+    assert!(multipart::Form::try_from(job).is_ok());
+
+    let job = builder().set_reader("customers.csv".to_string(), std::io::empty()).build().unwrap();
+    assert_eq!(multipart::Form::try_from(job).err(), Some(WebexpressJobError::MissingCredentials));
+
+    let mut job = builder().set_file("assets/does_not_exist.csv").build().unwrap();
+    job.j_username = Some("user".to_string());
+    job.j_password = Some("password".to_string());
+    assert!(matches!(multipart::Form::try_from(job), Err(WebexpressJobError::FileNotReadable(_))));
+
+    #[allow(deprecated)]
+    let job = WebexpressJob::minimal("Customers".to_string(), "".to_string(), "distributionlist".to_string(), "assets/test.csv".to_string(), "utf-8".to_string(), "+49".to_string());
+    assert_eq!((job.dlu_type.as_str(), job.dlu_defaultcountrycode.as_str()), ("distributionlist", "+49"));
+    assert!(job.dlu_firstrowcolumnnames);
+}
+
+#[tokio::test]
+async fn test_job_form_with_credentials() {
+    use hyper::{service::{make_service_fn, service_fn}, Request, Response};
+    use crate::common::transport::Transporter;
+
+    let make_service = make_service_fn(|_| async {
+        Ok::<_, hyper::Error>(service_fn(|req: Request<hyper::Body>| async move {
+            let body = hyper::body::to_bytes(req.into_body()).await?;
+            Ok::<_, hyper::Error>(Response::new(hyper::Body::from(body)))
+        }))
+    });
+    let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+    let addr = server.local_addr();
+    tokio::spawn(server);
+
+    let mut job = WebexpressJob::builder()
+        .set_list_name("Customers".to_string())
+        .set_content("customers.csv".to_string(), b"name;fax\nRetarus;+498955280\n".to_vec())
+        .set_default_country_code(CountryCode::new(49).unwrap())
+        .build()
+        .unwrap();
+    job.j_username = Some("other".to_string());
+    let mut job = Some(job);
+    let transporter = Transporter::new(Credentials::new("user", "password"));
+    let res = transporter
+        .form_post_with(format!("http://{}/upload", addr), |credentials| Ok(job.take().unwrap().into_form_with(credentials)?))
+        .await
+        .unwrap();
+    let body = res.text().await.unwrap();
+    assert!(body.contains("name=\"j_username\"\r\n\r\nuser\r\n"));
+    assert!(body.contains("name=\"j_password\"\r\n\r\npassword\r\n"));
+}
+
+#[test]
+fn test_job_builder_validation() {
+    let builder = WebexpressJob::builder().set_file("assets/test.csv");
+    assert_eq!(builder.build().err(), Some(WebexpressJobError::MissingListName));
+
+    let builder = WebexpressJob::builder().set_list_name("Customers".to_string()).set_file("assets/test.csv");
+    assert_eq!(builder.build().err(), Some(WebexpressJobError::MissingCountryCode));
+
+    let job = WebexpressJob::builder()
+        .set_list_name("Customers".to_string())
+        .set_file("assets/test.csv")
+        .set_visibility(Visibility::Company)
+        .set_default_country_code(CountryCode::new(41).unwrap())
+        .set_first_row_column_names(false)
+        .build()
+        .unwrap();
+    assert_eq!(job.dlu_type, "distributionlist");
+    assert_eq!(job.dlu_defaultcountrycode, "+41");
+    assert!(!job.dlu_firstrowcolumnnames);
+}
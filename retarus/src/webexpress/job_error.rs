@@ -0,0 +1,25 @@
+use std::fmt;
+
+/// Returned by the [WebexpressJobBuilder](super::job::WebexpressJobBuilder) if a required value is missing
+/// and when a job can not be converted into an upload.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WebexpressJobError {
+    MissingListName,
+    MissingFile,
+    MissingCountryCode,
+    MissingCredentials,
+    FileNotReadable(String),
+}
+impl fmt::Display for WebexpressJobError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WebexpressJobError::MissingListName => write!(f, "The list name must not be empty"),
+            WebexpressJobError::MissingFile => write!(f, "A file or content for the list must be set"),
+            WebexpressJobError::MissingCountryCode => write!(f, "A default country code must be set"),
+            WebexpressJobError::MissingCredentials => write!(f, "The username and password for the upload are missing"),
+            WebexpressJobError::FileNotReadable(reason) => write!(f, "The list could not be read: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for WebexpressJobError {}
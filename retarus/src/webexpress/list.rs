@@ -0,0 +1,342 @@
+use std::{collections::HashMap, fmt};
+
+use crate::common::phone::{CountryCode, PhoneNumber};
+
+use super::job::Charset;
+
+/// The columns every list starts with, custom fields are appended after them.
+pub const COLUMNS: [&str; 6] = ["id", "first_name", "last_name", "email", "SMS", "FAX"];
+
+/// A single entry of a distribution list.
+///
+/// ## Example
+/// ```rust
+/// use retarus::common::phone::PhoneNumber;
+/// use retarus::webexpress::job::{Charset, WebexpressJob};
+/// use retarus::webexpress::list::{Contact, ListWriter};
+///
+/// let mut writer = ListWriter::new(Charset::Utf8);
+/// writer.add_contact(
+///     Contact::new("Hazel", "Grealish")
+///         .set_fax(PhoneNumber::parse("+49 89 5528 0", None).unwrap())
+///         .set_field("customer_id", "4711"),
+/// );
+/// let job = WebexpressJob::builder()
+///     .set_list_name("Customers".to_string())
+///     .set_content("customers.csv".to_string(), writer.to_bytes())
+///     .set_charset(writer.charset())
+///     .set_default_country_code("+49".parse().unwrap())
+///     .build();
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Contact {
+    /// Written to the id column, the number of the row in the list if it is not set.
+    pub id: Option<String>,
+    pub first_name: String,
+    pub last_name: String,
+    pub email: Option<String>,
+    pub mobile: Option<PhoneNumber>,
+    pub fax: Option<PhoneNumber>,
+    pub fields: Vec<(String, String)>,
+}
+impl Contact {
+    pub fn new(first_name: &str, last_name: &str) -> Contact {
+        Contact { first_name: first_name.to_string(), last_name: last_name.to_string(), ..Contact::default() }
+    }
+
+    pub fn set_id(mut self, id: &str) -> Contact {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    pub fn set_email(mut self, email: &str) -> Contact {
+        self.email = Some(email.to_string());
+        self
+    }
+
+    pub fn set_mobile(mut self, mobile: PhoneNumber) -> Contact {
+        self.mobile = Some(mobile);
+        self
+    }
+
+    pub fn set_fax(mut self, fax: PhoneNumber) -> Contact {
+        self.fax = Some(fax);
+        self
+    }
+
+    /// Set a custom field, it is written as additional column with the name as header.
+    pub fn set_field(mut self, name: &str, value: &str) -> Contact {
+        match self.fields.iter_mut().find(|(n, _)| n == name) {
+            Some(field) => field.1 = value.to_string(),
+            None => self.fields.push((name.to_string(), value.to_string())),
+        }
+        self
+    }
+}
+
+/// Writes contacts in the csv layout the list import expects, see [COLUMNS].
+#[derive(Debug, Clone)]
+pub struct ListWriter {
+    charset: Charset,
+    contacts: Vec<Contact>,
+}
+impl ListWriter {
+    pub fn new(charset: Charset) -> ListWriter {
+        ListWriter { charset, contacts: vec![] }
+    }
+
+    pub fn add_contact(&mut self, contact: Contact) {
+        self.contacts.push(contact);
+    }
+
+    /// The charset of the written list, set it on the job with [WebexpressJobBuilder::set_charset](super::job::WebexpressJobBuilder::set_charset).
+    pub fn charset(&self) -> Charset {
+        self.charset
+    }
+
+    /// The list as csv text, custom fields get a column in the order they first appear.
+    pub fn to_csv(&self) -> String {
+        let mut custom: Vec<&str> = vec![];
+        for contact in self.contacts.iter() {
+            for (name, _) in contact.fields.iter() {
+                if !custom.contains(&name.as_str()) {
+                    custom.push(name);
+                }
+            }
+        }
+
+        let mut csv = String::new();
+        let header: Vec<&str> = COLUMNS.iter().copied().chain(custom.iter().copied()).collect();
+        write_row(&mut csv, &header);
+        for (index, contact) in self.contacts.iter().enumerate() {
+            let id = contact.id.clone().unwrap_or_else(|| (index + 1).to_string());
+            let mobile = contact.mobile.as_ref().map(PhoneNumber::e164).unwrap_or_default();
+            let fax = contact.fax.as_ref().map(PhoneNumber::e164).unwrap_or_default();
+            let mut row = vec![
+                id.as_str(),
+                contact.first_name.as_str(),
+                contact.last_name.as_str(),
+                contact.email.as_deref().unwrap_or(""),
+                mobile.as_str(),
+                fax.as_str(),
+            ];
+            for name in custom.iter() {
+                let value = contact.fields.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str());
+                row.push(value.unwrap_or(""));
+            }
+            write_row(&mut csv, &row);
+        }
+        csv
+    }
+
+    /// The list encoded in its charset, ready to be uploaded with [WebexpressJobBuilder::set_content](super::job::WebexpressJobBuilder::set_content).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self.charset {
+            Charset::Utf8 => self.to_csv().into_bytes(),
+        }
+    }
+}
+
+fn write_row(csv: &mut String, values: &[&str]) {
+    let escaped: Vec<String> = values
+        .iter()
+        .map(|v| {
+            if v.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", v.replace('"', "\"\""))
+            } else {
+                v.to_string()
+            }
+        })
+        .collect();
+    csv.push_str(&escaped.join(","));
+    csv.push_str("\r\n");
+}
+
+/// A problem found by [validate_list], rows are the lines on which the records start, counted from 1 with the header.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ListIssue {
+    /// The list has none of the SMS, FAX or email columns.
+    MissingContactColumn,
+    DuplicateColumn(String),
+    WrongFieldCount { row: usize, expected: usize, found: usize },
+    InvalidNumber { row: usize, column: String, value: String },
+    /// The number or email address already appeared in an earlier row.
+    Duplicate { row: usize, first_row: usize, value: String },
+}
+impl fmt::Display for ListIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ListIssue::MissingContactColumn => write!(f, "The list needs at least one of the columns SMS, FAX or email"),
+            ListIssue::DuplicateColumn(name) => write!(f, "The column {} appears more than once", name),
+            ListIssue::WrongFieldCount { row, expected, found } => write!(f, "Row {}: expected {} fields, found {}", row, expected, found),
+            ListIssue::InvalidNumber { row, column, value } => write!(f, "Row {}: invalid {} number \"{}\"", row, column, value),
+            ListIssue::Duplicate { row, first_row, value } => write!(f, "Row {}: \"{}\" is a duplicate of row {}", row, value, first_row),
+        }
+    }
+}
+
+/// Checks an existing csv list before it is uploaded: the columns, the number formats and duplicate entries.
+/// With a default country code, like the one of the job, numbers without `+` or `00` are read as national numbers,
+/// e.g. `9369698995` with `+1`. Without it, numbers have to be in international format.
+pub fn validate_list(csv: &str, default_country: Option<CountryCode>) -> Vec<ListIssue> {
+    let mut issues = vec![];
+    let mut rows = read_records(csv).into_iter();
+    let header = match rows.next() {
+        Some((_, header)) => header,
+        None => return vec![ListIssue::MissingContactColumn],
+    };
+    for (i, name) in header.iter().enumerate() {
+        if header[..i].iter().any(|n| n.eq_ignore_ascii_case(name)) {
+            issues.push(ListIssue::DuplicateColumn(name.to_string()));
+        }
+    }
+    let column = |name: &str| header.iter().position(|n| n.eq_ignore_ascii_case(name));
+    let number_columns: Vec<(usize, &str)> = ["SMS", "FAX"].iter().filter_map(|n| Some((column(n)?, *n))).collect();
+    let email = column("email");
+    if number_columns.is_empty() && email.is_none() {
+        issues.push(ListIssue::MissingContactColumn);
+    }
+
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    for (row, values) in rows {
+        if values.len() != header.len() {
+            issues.push(ListIssue::WrongFieldCount { row, expected: header.len(), found: values.len() });
+            continue;
+        }
+        for (i, name) in number_columns.iter() {
+            let value = values[*i].trim();
+            if value.is_empty() {
+                continue;
+            }
+            match PhoneNumber::parse(value, default_country) {
+                Ok(number) => {
+                    let key = format!("{}:{}", name, number.e164());
+                    if let Some(first_row) = seen.insert(key, row) {
+                        issues.push(ListIssue::Duplicate { row, first_row, value: value.to_string() });
+                    }
+                }
+                Err(_) => issues.push(ListIssue::InvalidNumber { row, column: name.to_string(), value: value.to_string() }),
+            }
+        }
+        if let Some(value) = email.map(|i| values[i].trim().to_lowercase()).filter(|v| !v.is_empty()) {
+            if let Some(first_row) = seen.insert(format!("email:{}", value), row) {
+                issues.push(ListIssue::Duplicate { row, first_row, value });
+            }
+        }
+    }
+    issues
+}
+
+/// Splits csv text into records with the line they start on, quoted values may contain commas, line breaks and escaped quotes.
+/// Empty lines are skipped.
+fn read_records(csv: &str) -> Vec<(usize, Vec<String>)> {
+    let mut records = vec![];
+    let mut values = vec![];
+    let mut value = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut start = 1;
+    let mut chars = csv.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                value.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => values.push(std::mem::take(&mut value)),
+            '\r' if !quoted && matches!(chars.peek(), Some('\n') | None) => {}
+            '\n' if !quoted => {
+                values.push(std::mem::take(&mut value));
+                push_record(&mut records, start, std::mem::take(&mut values));
+                line += 1;
+                start = line;
+            }
+            _ => {
+                if c == '\n' {
+                    line += 1;
+                }
+                value.push(c);
+            }
+        }
+    }
+    values.push(value);
+    push_record(&mut records, start, values);
+    records
+}
+
+fn push_record(records: &mut Vec<(usize, Vec<String>)>, start: usize, values: Vec<String>) {
+    if values.len() > 1 || !values[0].trim().is_empty() {
+        records.push((start, values));
+    }
+}
+
+#[test]
+fn test_write_list() {
+    let mut writer = ListWriter::new(Charset::Utf8);
+    writer.add_contact(Contact::new("Hazel", "Grealish").set_fax(PhoneNumber::parse("089 55280", CountryCode::new(49).ok()).unwrap()));
+    writer.add_contact(
+        Contact::new("Jürgen", "Müller, Jr.")
+            .set_id("4711")
+            .set_email("jm@example.com")
+            .set_mobile(PhoneNumber::parse("+4917600000000", None).unwrap())
+            .set_field("note", "says \"hi\""),
+    );
+    let csv = writer.to_csv();
+    assert_eq!(
+        csv,
+        "id,first_name,last_name,email,SMS,FAX,note\r\n1,Hazel,Grealish,,,+498955280,\r\n4711,Jürgen,\"Müller, Jr.\",jm@example.com,+4917600000000,,\"says \"\"hi\"\"\"\r\n"
+    );
+    assert_eq!(writer.to_bytes(), csv.as_bytes());
+
+    // the written list has the layout of the list the integration tests import
+    let fixture = include_str!("../../../retarus-tests/assets/test.csv");
+    assert_eq!(fixture.lines().next(), Some(COLUMNS.join(",").as_str()));
+}
+
+#[test]
+fn test_validate_list() {
+    let csv = "first_name,last_name,email,SMS,FAX\n\
+               Hazel,Grealish,hg@example.com,+4917600000000,089 55280\n\
+               Hazel,Grealish,HG@example.com,,0049 89 55280\n\
+               Maritsa,Genicke,,12ab,\n\
+               Adelice,Blaber\n";
+    let issues = validate_list(csv, Some(CountryCode::new(49).unwrap()));
+    assert_eq!(
+        issues,
+        vec![
+            ListIssue::Duplicate { row: 3, first_row: 2, value: "0049 89 55280".to_string() },
+            ListIssue::Duplicate { row: 3, first_row: 2, value: "hg@example.com".to_string() },
+            ListIssue::InvalidNumber { row: 4, column: "SMS".to_string(), value: "12ab".to_string() },
+            ListIssue::WrongFieldCount { row: 5, expected: 5, found: 2 },
+        ]
+    );
+    assert_eq!(validate_list("id,name\n1,Hazel", None), vec![ListIssue::MissingContactColumn]);
+    assert!(validate_list("first_name,email,FAX\nHazel,hg@example.com,+498955280\r\n", None).is_empty());
+
+    // the numbers of the fixture are national numbers of the default country, not international ones
+    let fixture = include_str!("../../../retarus-tests/assets/test.csv");
+    assert!(validate_list(fixture, Some(CountryCode::new(1).unwrap())).is_empty());
+    let issues = validate_list("id,SMS\n1,9369698995\n2,+19369698995", Some(CountryCode::new(1).unwrap()));
+    assert_eq!(issues, vec![ListIssue::Duplicate { row: 3, first_row: 2, value: "+19369698995".to_string() }]);
+}
+
+#[test]
+fn test_validate_written_list() {
+    let mut writer = ListWriter::new(Charset::Utf8);
+    writer.add_contact(
+        Contact::new("Hazel", "Grealish")
+            .set_mobile(PhoneNumber::parse("+4917600000000", None).unwrap())
+            .set_field("address", "Marktplatz 1,\r\n80331 \"Altstadt\""),
+    );
+    writer.add_contact(Contact::new("Maritsa", "Genicke").set_mobile(PhoneNumber::parse("+4917600000000", None).unwrap()));
+    let issues = validate_list(&writer.to_csv(), None);
+    assert_eq!(issues, vec![ListIssue::Duplicate { row: 4, first_row: 2, value: "+4917600000000".to_string() }]);
+
+    let records = read_records("id,note\n1,\"a\nb\"\n\n2,c");
+    assert_eq!(records, vec![
+        (1, vec!["id".to_string(), "note".to_string()]),
+        (2, vec!["1".to_string(), "a\nb".to_string()]),
+        (5, vec!["2".to_string(), "c".to_string()]),
+    ]);
+}
@@ -1,2 +1,5 @@
 pub mod client;
-pub mod job;
\ No newline at end of file
+pub mod import;
+pub mod job;
+pub mod job_error;
+pub mod list;